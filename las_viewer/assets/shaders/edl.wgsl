@@ -0,0 +1,42 @@
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+struct EdlUniform {
+    strength: f32,
+    radius: f32,
+};
+
+const PI: f32 = 3.14159265;
+const NEIGHBOURS: i32 = 8;
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var depth_texture: texture_depth_2d;
+@group(0) @binding(2) var<uniform> settings: EdlUniform;
+
+// Depth is reversed, 0 is infinitely far and clears the background. The log of the linear depth
+// of a neighbour minus that of the centre is log2(depth) - log2(neighbour depth), so the near
+// plane is not needed.
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    let color = textureLoad(screen_texture, pixel, 0);
+    let depth = textureLoad(depth_texture, pixel, 0);
+    if depth <= 0.0 {
+        return color;
+    }
+
+    let last = vec2<i32>(textureDimensions(depth_texture)) - 1;
+    var response = 0.0;
+    for (var i = 0; i < NEIGHBOURS; i++) {
+        let angle = f32(i) * 2.0 * PI / f32(NEIGHBOURS);
+        let offset = vec2<i32>(round(vec2<f32>(cos(angle), sin(angle)) * settings.radius));
+        let neighbour_depth = textureLoad(depth_texture, clamp(pixel + offset, vec2<i32>(0), last), 0);
+        // Only neighbours in front of the centre darken it.
+        if neighbour_depth > 0.0 {
+            response += max(0.0, log2(neighbour_depth) - log2(depth));
+        }
+    }
+    response /= f32(NEIGHBOURS);
+
+    let shade = exp(-response * 300.0 * settings.strength);
+    return vec4<f32>(color.rgb * shade, color.a);
+}
@@ -0,0 +1,90 @@
+#import bevy_pbr::mesh_functions::{get_world_from_local, mesh_position_local_to_clip}
+#import bevy_pbr::mesh_view_bindings::view
+
+struct SplatMaterial {
+    color: vec4<f32>,
+    size: f32,
+    spacing: f32,
+    min_size: f32,
+    max_size: f32,
+    size_mode: u32,
+    shape: u32,
+};
+
+const SIZE_FIXED: u32 = 0u;
+const SHAPE_ROUND: u32 = 0u;
+
+struct SplatPoint {
+    position: vec3<f32>,
+    // Zero alpha hides the point.
+    color: u32,
+};
+
+@group(2) @binding(0) var<uniform> material: SplatMaterial;
+@group(2) @binding(1) var<storage, read> points: array<SplatPoint>;
+
+// Every point is drawn with six vertices, the indices of the mesh count up from zero.
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @builtin(vertex_index) vertex_index: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    // Index meshes are shared by materials with fewer points, their extra vertices and the ones
+    // of hidden points collapse to a point outside of the view.
+    let index = vertex.vertex_index / 6u;
+    if index >= arrayLength(&points) {
+        out.position = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+        return out;
+    }
+    let point = points[index];
+    let color = unpack4x8unorm(point.color);
+    if color.a == 0.0 {
+        out.position = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+        return out;
+    }
+
+    // Corners of the two triangles of the splat, from (-1, -1) to (1, 1).
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex.vertex_index % 6u];
+
+    var clip = mesh_position_local_to_clip(get_world_from_local(vertex.instance_index), vec4<f32>(point.position, 1.0));
+
+    // Diameter of the splat in pixels.
+    var size = material.size;
+    if material.size_mode != SIZE_FIXED {
+        // Projects a splat of `size` times the point spacing, so splats shrink with distance.
+        let pixels_per_unit = view.clip_from_view[1][1] * view.viewport.w * 0.5;
+        size = clamp(material.size * material.spacing * pixels_per_unit / clip.w, material.min_size, material.max_size);
+    }
+    // Offset in pixels, scaled by w so it stays the same after the perspective divide.
+    clip = vec4<f32>(clip.xy + corner * size / view.viewport.zw * clip.w, clip.zw);
+
+    out.position = clip;
+    out.corner = corner;
+    out.color = material.color * color;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    if material.shape == SHAPE_ROUND && dot(in.corner, in.corner) > 1.0 {
+        discard;
+    }
+    return in.color;
+}
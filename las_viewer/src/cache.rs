@@ -0,0 +1,253 @@
+use std::{
+    fs,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use bincode::Options;
+use octree::PointAttributes;
+use serde::{Deserialize, Serialize};
+
+use crate::TilePoints;
+
+// Bumped whenever the content of an entry changes, entries of other versions are rebuilt.
+// Version 2 stores points relative to the minimum of the header bounds, version 3 adds the
+// attributes of every point, version 4 the magic in front of the version.
+const CACHE_VERSION: u32 = 4;
+const CACHE_FILE_EXTENSION: &str = "cached_points";
+// Entries are written under this extension and renamed once complete.
+const TEMPORARY_FILE_EXTENSION: &str = "tmp";
+// Every entry starts with the magic and the version, so other files are rejected before any of
+// their content is deserialized.
+const CACHE_MAGIC: [u8; 8] = *b"LASVCACH";
+// Entry headers are a few hundred bytes, bigger ones are damaged.
+const MAX_ENTRY_HEADER_SIZE: u64 = 1 << 16;
+
+// Identifies the exact input an entry was built from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SourceKey {
+    pub path: PathBuf,
+    pub size: u64,
+    // Seconds and nanoseconds since the unix epoch.
+    pub modified: Option<(u64, u32)>,
+    pub header_hash: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CachedHeader {
+    pub version: (u8, u8),
+    pub point_format: u8,
+    pub number_of_points: u64,
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+    pub system_identifier: String,
+    pub generating_software: String,
+}
+
+// Written in front of the points, so an entry can be checked without reading them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct EntryHeader {
+    source: SourceKey,
+    header: CachedHeader,
+}
+
+impl SourceKey {
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let path = fs::canonicalize(path)?;
+        let metadata = fs::metadata(&path)?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| (modified.as_secs(), modified.subsec_nanos()));
+
+        Ok(Self {
+            header_hash: hash_header(&path)?,
+            size: metadata.len(),
+            modified,
+            path,
+        })
+    }
+}
+
+impl CachedHeader {
+    pub fn from_las(header: &las::Header) -> Self {
+        let version = header.version();
+        let bounds = header.bounds();
+        Self {
+            version: (version.major, version.minor),
+            point_format: header.point_format().to_u8().unwrap_or(u8::MAX),
+            number_of_points: header.number_of_points(),
+            min: [bounds.min.x, bounds.min.y, bounds.min.z],
+            max: [bounds.max.x, bounds.max.y, bounds.max.z],
+            system_identifier: header.system_identifier().to_string(),
+            generating_software: header.generating_software().to_string(),
+        }
+    }
+}
+
+// Entries are named after the input and a hash of its full path, so inputs with the same name
+// in different directories get their own entry.
+pub fn entry_path(cache_dir: &Path, source: &SourceKey) -> PathBuf {
+    let name = source.path.file_name().unwrap_or_default().to_string_lossy();
+    let path_hash = fnv1a(source.path.to_string_lossy().as_bytes());
+    cache_dir.join(format!("{name}-{path_hash:016x}.{CACHE_FILE_EXTENSION}"))
+}
+
+// Points cached for `path`, `None` when there is no entry or it was built from something else.
+pub fn load(cache_dir: &Path, path: &Path, header: &las::Header) -> Option<TilePoints> {
+    let source = SourceKey::from_path(path).ok()?;
+    let entry_path = entry_path(cache_dir, &source);
+    let file = fs::File::open(&entry_path).ok()?;
+    let file_size = file.metadata().ok()?.len();
+    let mut reader = BufReader::new(file);
+
+    let expected = EntryHeader {
+        source,
+        header: CachedHeader::from_las(header),
+    };
+    match read_entry_header(&mut reader, file_size) {
+        Some(entry_header) if entry_header == expected => {}
+        Some(_) | None => {
+            println!("cache entry {} is out of date, rebuilding it", entry_path.display());
+            return None;
+        }
+    }
+
+    println!("reading cached points from {}...", entry_path.display());
+    // The points cannot take more bytes than the file has, which bounds what a damaged length
+    // can allocate.
+    match options().with_limit(file_size).deserialize_from::<_, TilePoints>(&mut reader) {
+        Ok(cached) => Some(cached),
+        Err(error) => {
+            println!("cache entry {} is damaged ({error}), rebuilding it", entry_path.display());
+            None
+        }
+    }
+}
+
+pub fn store(
+    cache_dir: &Path,
+    path: &Path,
+    header: &las::Header,
+    points: &[[f32; 3]],
+    attributes: &[PointAttributes],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let source = SourceKey::from_path(path)?;
+    let entry_path = entry_path(cache_dir, &source);
+    let entry_header = EntryHeader {
+        source,
+        header: CachedHeader::from_las(header),
+    };
+
+    // Written next to the entry and renamed, so an interrupted write never leaves a damaged entry.
+    fs::create_dir_all(cache_dir)?;
+    let temporary_path = entry_path.with_extension(TEMPORARY_FILE_EXTENSION);
+    let mut writer = BufWriter::new(fs::File::create(&temporary_path)?);
+    writer.write_all(&CACHE_MAGIC)?;
+    writer.write_all(&CACHE_VERSION.to_le_bytes())?;
+    options().serialize_into(&mut writer, &entry_header)?;
+    options().serialize_into(&mut writer, &(points, attributes))?;
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&temporary_path, &entry_path)?;
+
+    Ok(())
+}
+
+// Removes every entry and every unfinished write, returns how many files were removed.
+pub fn clear(cache_dir: &Path) -> io::Result<usize> {
+    let mut removed = 0;
+    for path in files(cache_dir, CACHE_FILE_EXTENSION)?
+        .into_iter()
+        .chain(files(cache_dir, TEMPORARY_FILE_EXTENSION)?)
+    {
+        fs::remove_file(&path)?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+// Removes entries whose input is gone or changed, entries of other cache versions and the
+// temporary files of interrupted writes.
+pub fn prune(cache_dir: &Path) -> io::Result<usize> {
+    let mut removed = 0;
+    for temporary_path in files(cache_dir, TEMPORARY_FILE_EXTENSION)? {
+        fs::remove_file(&temporary_path)?;
+        removed += 1;
+    }
+    for entry_path in files(cache_dir, CACHE_FILE_EXTENSION)? {
+        let entry_header = fs::File::open(&entry_path).ok().and_then(|file| {
+            let file_size = file.metadata().ok()?.len();
+            read_entry_header(&mut BufReader::new(file), file_size)
+        });
+
+        let is_current = entry_header.is_some_and(|entry_header| {
+            SourceKey::from_path(&entry_header.source.path).is_ok_and(|source| source == entry_header.source)
+        });
+        if !is_current {
+            fs::remove_file(&entry_path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+// Fixed size integers, like the free functions of bincode.
+fn options() -> impl Options {
+    bincode::DefaultOptions::new().with_fixint_encoding().allow_trailing_bytes()
+}
+
+// `None` for files without the magic, of other versions or with a damaged entry header.
+fn read_entry_header(reader: &mut impl Read, file_size: u64) -> Option<EntryHeader> {
+    let mut prefix = [0; CACHE_MAGIC.len() + 4];
+    reader.read_exact(&mut prefix).ok()?;
+    let (magic, version) = prefix.split_at(CACHE_MAGIC.len());
+    if magic != CACHE_MAGIC || u32::from_le_bytes(version.try_into().ok()?) != CACHE_VERSION {
+        return None;
+    }
+
+    options()
+        .with_limit(MAX_ENTRY_HEADER_SIZE.min(file_size))
+        .deserialize_from(reader)
+        .ok()
+}
+
+// Files of the cache directory with the given extension.
+fn files(cache_dir: &Path, file_extension: &str) -> io::Result<Vec<PathBuf>> {
+    if !cache_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    for entry in fs::read_dir(cache_dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|extension| extension == file_extension) {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+// Hash of the public header block, its size is stored at byte 94.
+fn hash_header(path: &Path) -> io::Result<u64> {
+    let mut bytes = Vec::new();
+    fs::File::open(path)?.take(u16::MAX as u64).read_to_end(&mut bytes)?;
+
+    let header_size = match bytes.get(94..96) {
+        Some(size) => u16::from_le_bytes([size[0], size[1]]) as usize,
+        None => bytes.len(),
+    };
+    Ok(fnv1a(&bytes[..header_size.min(bytes.len())]))
+}
+
+// Stable across builds, unlike the hasher of the standard library.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
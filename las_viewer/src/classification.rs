@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+
+use bevy::{prelude::*, render::renderer::RenderQueue};
+use octree::PointAttributes;
+
+use crate::{
+    color::{classification_color, PointColors},
+    lod::LodTree,
+    splat::{self, SplatMaterial, SplatPoint},
+};
+
+const PANEL_FONT_SIZE: f32 = 16.0;
+const SHOWN_TEXT: Color = Color::srgb(0.9, 0.9, 0.9);
+const HIDDEN_TEXT: Color = Color::srgb(0.45, 0.45, 0.45);
+
+// Which classifications of the point mesh are drawn, and how many points each has.
+#[derive(Resource)]
+pub struct ClassVisibility {
+    pub counts: BTreeMap<u8, usize>,
+    pub hidden: [bool; 256],
+}
+
+// Button of the classification panel that toggles one class.
+#[derive(Component)]
+pub struct ClassToggle(pub u8);
+
+#[derive(Component)]
+pub struct ClassLabel(pub u8);
+
+impl ClassVisibility {
+    pub fn from_attributes(attributes: &[PointAttributes]) -> Self {
+        let mut counts = BTreeMap::new();
+        for attributes in attributes.iter() {
+            *counts.entry(attributes.classification).or_insert(0) += 1;
+        }
+        Self { counts, hidden: [false; 256] }
+    }
+
+    pub fn is_visible(&self, classification: u8) -> bool {
+        !self.hidden[classification as usize]
+    }
+
+    // Splats of hidden points get zero alpha, which the shader drops, after `PointColors::apply`.
+    pub fn apply(&self, splats: &mut [SplatPoint], attributes: &[PointAttributes]) {
+        for (splat, attributes) in splats.iter_mut().zip(attributes) {
+            if !self.is_visible(attributes.classification) {
+                splat.color &= 0x00ff_ffff;
+            }
+        }
+    }
+
+    fn label(&self, classification: u8) -> String {
+        let mark = if self.is_visible(classification) { "x" } else { " " };
+        let count = self.counts.get(&classification).copied().unwrap_or(0);
+        format!("[{mark}] {classification:>3} {:<18} {count}", classification_name(classification))
+    }
+}
+
+// ASPRS standard classes of LAS 1.4.
+pub fn classification_name(classification: u8) -> &'static str {
+    match classification {
+        0 => "never classified",
+        1 => "unassigned",
+        2 => "ground",
+        3 => "low vegetation",
+        4 => "medium vegetation",
+        5 => "high vegetation",
+        6 => "building",
+        7 => "low noise",
+        8 => "model key point",
+        9 => "water",
+        10 => "rail",
+        11 => "road surface",
+        12 => "overlap",
+        13 => "wire guard",
+        14 => "wire conductor",
+        15 => "transmission tower",
+        16 => "wire connector",
+        17 => "bridge deck",
+        18 => "high noise",
+        19..=63 => "reserved",
+        64.. => "user defined",
+    }
+}
+
+// One button per classification present in the points, in the top left corner.
+pub fn spawn_panel(commands: &mut Commands, visibility: &ClassVisibility) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(8.0),
+                left: Val::Px(8.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(6.0)),
+                row_gap: Val::Px(2.0),
+                ..default()
+            },
+            background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .with_children(|panel| {
+            panel.spawn(TextBundle::from_section(
+                "classes (click to toggle, A shows all)",
+                TextStyle { font_size: PANEL_FONT_SIZE, color: SHOWN_TEXT, ..default() },
+            ));
+
+            for &classification in visibility.counts.keys() {
+                let [red, green, blue, _] = classification_color(classification);
+                panel
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::Row,
+                                align_items: AlignItems::Center,
+                                column_gap: Val::Px(6.0),
+                                ..default()
+                            },
+                            background_color: Color::NONE.into(),
+                            ..default()
+                        },
+                        ClassToggle(classification),
+                    ))
+                    .with_children(|button| {
+                        button.spawn(NodeBundle {
+                            style: Style { width: Val::Px(10.0), height: Val::Px(10.0), ..default() },
+                            background_color: Color::srgb(red, green, blue).into(),
+                            ..default()
+                        });
+                        button.spawn((
+                            TextBundle::from_section(
+                                visibility.label(classification),
+                                TextStyle { font_size: PANEL_FONT_SIZE, color: SHOWN_TEXT, ..default() },
+                            ),
+                            ClassLabel(classification),
+                        ));
+                    });
+            }
+        });
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn toggle_classes(
+    keys: Res<ButtonInput<KeyCode>>,
+    buttons: Query<(&Interaction, &ClassToggle), Changed<Interaction>>,
+    mut labels: Query<(&mut Text, &ClassLabel)>,
+    visibility: Option<ResMut<ClassVisibility>>,
+    colors: Option<Res<PointColors>>,
+    lod: Option<Res<LodTree>>,
+    materials: Res<Assets<SplatMaterial>>,
+    queue: Res<RenderQueue>,
+) {
+    let (Some(mut visibility), Some(colors), Some(lod)) = (visibility, colors, lod) else {
+        return;
+    };
+
+    let mut changed = false;
+    for (interaction, toggle) in buttons.iter() {
+        if *interaction == Interaction::Pressed {
+            let hidden = &mut visibility.hidden[toggle.0 as usize];
+            *hidden = !*hidden;
+            changed = true;
+        }
+    }
+    if keys.just_pressed(KeyCode::KeyA) {
+        visibility.hidden = [false; 256];
+        changed = true;
+    }
+    if !changed {
+        return;
+    }
+
+    for (node, material) in lod.loaded() {
+        if let Some(material) = materials.get(material) {
+            splat::write_splat_buffer(&queue, &material.points, &lod.gen_splats(node, &colors, &visibility));
+        }
+    }
+    for (mut text, label) in labels.iter_mut() {
+        let color = if visibility.is_visible(label.0) { SHOWN_TEXT } else { HIDDEN_TEXT };
+        text.sections[0].value = visibility.label(label.0);
+        text.sections[0].style.color = color;
+    }
+}
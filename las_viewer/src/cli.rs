@@ -0,0 +1,500 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::Resource;
+use octree::{Division, DuplicateWinner, LooseInsertion, OctreeBuilder, OctreeError, SphereMode};
+
+use crate::{
+    color::{ColorMode, ColorSettings, Ramp},
+    edl::EdlSettings,
+    splat::{Shape, SizeMode, SplatSettings},
+};
+
+pub const USAGE: &str = "usage: las_viewer [OPTIONS] <INPUT>...
+
+arguments:
+  <INPUT>...             LAS or LAZ files, or directories of them, shown as one scene
+
+options:
+  --cache-dir <DIR>      directory for cached points [default: .]
+  --depth <N>            octree depth, the starting depth with --capacity [default: 3]
+  --capacity <N>         divide leaves holding more than N points, down to --max-depth
+  --max-depth <N>        deepest octree level used with --capacity [default: 10]
+  --transform <MODE>     move, center or none [default: move]
+  --color <MODE>         uniform, rgb, intensity, elevation, classification, return-number
+                         or point-source, C cycles through them [default: uniform]
+  --ramp <RAMP>          elevation colours, rainbow, viridis or grayscale [default: rainbow]
+  --intensity-gamma <G>  gamma applied to stretched intensities [default: 1]
+  --intensity-clip <P>   percent of the lowest and highest intensities clipped [default: 2]
+  --point-size <S>       splat size, pixels or multiples of the point spacing, +/- change it
+                         [default: 2]
+  --size-mode <MODE>     fixed or attenuated, M switches them [default: fixed]
+  --shape <SHAPE>        round or square, S switches them [default: round]
+  --point-budget <N>     most points shown at once, nearer parts get more detail
+                         [default: 3000000]
+  --edl-strength <S>     eye-dome lighting strength, [ and ] change it [default: 1]
+  --edl-radius <R>       eye-dome lighting radius in pixels, shift with [ and ] changes it
+                         [default: 1.4]
+  --no-edl               start with eye-dome lighting off, E turns it on and off
+  --dedup <TOLERANCE>    merge points closer than TOLERANCE, off unless given
+  --dedup-winner <W>     point kept of merged ones, first, last, highest-intensity or
+                         first-return [default: first]
+  --surface <PATH>       reconstruct a surface mesh and write it as OBJ or PLY, picked by the
+                         extension of PATH
+  --show-surface         reconstruct a surface mesh and show it with the points
+  -v, --verbose          print the header and the LAZ chunk layout of every input
+  --clear-cache          remove every cache entry in the cache directory and exit
+  --prune-cache          remove cache entries of changed or missing inputs and unfinished writes and exit
+  -h, --help             print this message";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TransformMode {
+    // Moves the minimum corner of the bounds to the origin.
+    #[default]
+    Move,
+    // Centres the points horizontally and puts the lowest point at zero height.
+    Center,
+    // Keeps the coordinates of the file.
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceFormat {
+    Obj,
+    Ply,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheCommand {
+    Clear,
+    Prune,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct Args {
+    pub inputs: Vec<PathBuf>,
+    pub cache_dir: PathBuf,
+    pub depth: usize,
+    pub capacity: Option<usize>,
+    pub max_depth: usize,
+    pub transform: TransformMode,
+    pub color: ColorMode,
+    pub color_settings: ColorSettings,
+    pub splat: SplatSettings,
+    pub point_budget: usize,
+    pub edl: EdlSettings,
+    // Tolerance of duplicate removal, which only runs when it is set.
+    pub dedup: Option<f32>,
+    pub dedup_winner: DuplicateWinner,
+    // Where to write the reconstructed surface, a .obj or .ply file.
+    pub surface: Option<PathBuf>,
+    pub show_surface: bool,
+    pub verbose: bool,
+    // Set when the viewer should only maintain the cache.
+    pub cache_command: Option<CacheCommand>,
+}
+
+#[derive(Debug)]
+pub enum ArgsError {
+    Help,
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue { option: String, value: String, expected: &'static str },
+    NoInputs,
+    EmptyDirectory(PathBuf),
+    InputNotFound(PathBuf),
+    InputNotAFile(PathBuf),
+    UnsupportedInput(PathBuf),
+    InvalidInput { path: PathBuf, message: String },
+    InvalidOctree(OctreeError),
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            inputs: Vec::new(),
+            cache_dir: PathBuf::from("."),
+            depth: 3,
+            capacity: None,
+            max_depth: 10,
+            transform: TransformMode::Move,
+            color: ColorMode::Uniform,
+            color_settings: ColorSettings::default(),
+            splat: SplatSettings::default(),
+            point_budget: 3_000_000,
+            edl: EdlSettings::default(),
+            dedup: None,
+            dedup_winner: DuplicateWinner::First,
+            surface: None,
+            show_surface: false,
+            verbose: false,
+            cache_command: None,
+        }
+    }
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgsError::Help => {
+                write!(f, "{USAGE}")
+            }
+            ArgsError::UnknownOption(option) => {
+                write!(f, "unknown option {option}")
+            }
+            ArgsError::MissingValue(option) => {
+                write!(f, "{option} needs a value")
+            }
+            ArgsError::InvalidValue { option, value, expected } => {
+                write!(f, "invalid value {value:?} for {option}, expected {expected}")
+            }
+            ArgsError::NoInputs => {
+                write!(f, "no input files given")
+            }
+            ArgsError::EmptyDirectory(path) => {
+                write!(f, "directory {} contains no .las or .laz files", path.display())
+            }
+            ArgsError::InputNotFound(path) => {
+                write!(f, "input {} does not exist", path.display())
+            }
+            ArgsError::InputNotAFile(path) => {
+                write!(f, "input {} is not a file", path.display())
+            }
+            ArgsError::UnsupportedInput(path) => {
+                write!(f, "input {} is not a .las or .laz file", path.display())
+            }
+            ArgsError::InvalidInput { path, message } => {
+                write!(f, "input {} could not be opened: {message}", path.display())
+            }
+            ArgsError::InvalidOctree(error) => {
+                write!(f, "invalid octree options: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+impl Args {
+    pub fn from_env() -> Result<Self, ArgsError> {
+        let mut args = Self::parse(std::env::args().skip(1))?;
+        args.expand_directories()?;
+        args.validate()?;
+        Ok(args)
+    }
+
+    pub fn parse(mut arguments: impl Iterator<Item = String>) -> Result<Self, ArgsError> {
+        let mut args = Args::default();
+
+        while let Some(argument) = arguments.next() {
+            let mut value = |option: &str| arguments.next().ok_or_else(|| ArgsError::MissingValue(option.to_string()));
+
+            match argument.as_str() {
+                "-h" | "--help" => {
+                    return Err(ArgsError::Help);
+                }
+                "--cache-dir" => {
+                    args.cache_dir = PathBuf::from(value(&argument)?);
+                }
+                "--depth" => {
+                    args.depth = parse_number(&argument, value(&argument)?)?;
+                }
+                "--capacity" => {
+                    args.capacity = Some(parse_number(&argument, value(&argument)?)?);
+                }
+                "--max-depth" => {
+                    args.max_depth = parse_number(&argument, value(&argument)?)?;
+                }
+                "--transform" => {
+                    let mode = value(&argument)?;
+                    args.transform = match mode.as_str() {
+                        "move" => TransformMode::Move,
+                        "center" => TransformMode::Center,
+                        "none" => TransformMode::None,
+                        _ => return Err(invalid_value(&argument, mode, "move, center or none")),
+                    };
+                }
+                "--color" => {
+                    let mode = value(&argument)?;
+                    args.color = ColorMode::from_name(&mode).ok_or_else(|| {
+                        invalid_value(&argument, mode, "uniform, rgb, intensity, elevation, classification, return-number or point-source")
+                    })?;
+                }
+                "--ramp" => {
+                    let ramp = value(&argument)?;
+                    args.color_settings.ramp = Ramp::from_name(&ramp)
+                        .ok_or_else(|| invalid_value(&argument, ramp, "rainbow, viridis or grayscale"))?;
+                }
+                "--intensity-gamma" => {
+                    args.color_settings.intensity_gamma = parse_positive(&argument, value(&argument)?)?;
+                }
+                "--intensity-clip" => {
+                    args.color_settings.intensity_clip = parse_non_negative(&argument, value(&argument)?)?;
+                }
+                "--point-size" => {
+                    args.splat.size = parse_positive(&argument, value(&argument)?)?;
+                }
+                "--size-mode" => {
+                    let mode = value(&argument)?;
+                    args.splat.size_mode =
+                        SizeMode::from_name(&mode).ok_or_else(|| invalid_value(&argument, mode, "fixed or attenuated"))?;
+                }
+                "--shape" => {
+                    let shape = value(&argument)?;
+                    args.splat.shape = Shape::from_name(&shape).ok_or_else(|| invalid_value(&argument, shape, "round or square"))?;
+                }
+                "--point-budget" => {
+                    args.point_budget = parse_number(&argument, value(&argument)?)?;
+                }
+                "--edl-strength" => {
+                    args.edl.strength = parse_positive(&argument, value(&argument)?)?;
+                }
+                "--edl-radius" => {
+                    args.edl.radius = parse_positive(&argument, value(&argument)?)?;
+                }
+                "--no-edl" => {
+                    args.edl.enabled = false;
+                }
+                "--dedup" => {
+                    args.dedup = Some(parse_non_negative(&argument, value(&argument)?)?);
+                }
+                "--dedup-winner" => {
+                    let winner = value(&argument)?;
+                    args.dedup_winner = parse_winner(&winner)
+                        .ok_or_else(|| invalid_value(&argument, winner, "first, last, highest-intensity or first-return"))?;
+                }
+                "--surface" => {
+                    let path = value(&argument)?;
+                    if SurfaceFormat::from_path(Path::new(&path)).is_none() {
+                        return Err(invalid_value(&argument, path, "a path ending in .obj or .ply"));
+                    }
+                    args.surface = Some(PathBuf::from(path));
+                }
+                "--show-surface" => {
+                    args.show_surface = true;
+                }
+                "-v" | "--verbose" => {
+                    args.verbose = true;
+                }
+                "--clear-cache" => {
+                    args.cache_command = Some(CacheCommand::Clear);
+                }
+                "--prune-cache" => {
+                    args.cache_command = Some(CacheCommand::Prune);
+                }
+                "--" => {
+                    args.inputs.extend(arguments.by_ref().map(PathBuf::from));
+                }
+                _ if argument.starts_with('-') => {
+                    return Err(ArgsError::UnknownOption(argument));
+                }
+                _ => {
+                    args.inputs.push(PathBuf::from(argument));
+                }
+            }
+        }
+
+        Ok(args)
+    }
+
+    // Replaces every directory among the inputs by the LAS and LAZ files in it, sorted by name.
+    pub fn expand_directories(&mut self) -> Result<(), ArgsError> {
+        let mut inputs = Vec::with_capacity(self.inputs.len());
+        for path in self.inputs.drain(..) {
+            if !path.is_dir() {
+                inputs.push(path);
+                continue;
+            }
+
+            let entries = std::fs::read_dir(&path).map_err(|error| ArgsError::InvalidInput {
+                path: path.clone(),
+                message: error.to_string(),
+            })?;
+            let mut files: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|file| file.is_file() && is_point_cloud(file))
+                .collect();
+            if files.is_empty() {
+                return Err(ArgsError::EmptyDirectory(path));
+            }
+            files.sort();
+            inputs.extend(files);
+        }
+
+        self.inputs = inputs;
+        Ok(())
+    }
+
+    // Checks the inputs can be opened and the octree options form a valid layout.
+    pub fn validate(&self) -> Result<(), ArgsError> {
+        if self.cache_command.is_some() {
+            return Ok(());
+        }
+        if self.inputs.is_empty() {
+            return Err(ArgsError::NoInputs);
+        }
+
+        for path in self.inputs.iter() {
+            if !path.exists() {
+                return Err(ArgsError::InputNotFound(path.clone()));
+            }
+            if !path.is_file() {
+                return Err(ArgsError::InputNotAFile(path.clone()));
+            }
+            if !is_point_cloud(path) {
+                return Err(ArgsError::UnsupportedInput(path.clone()));
+            }
+            if let Err(error) = las::Reader::from_path(path) {
+                return Err(ArgsError::InvalidInput { path: path.clone(), message: error.to_string() });
+            }
+        }
+
+        self.octree_builder(1.0).validate().map_err(ArgsError::InvalidOctree)
+    }
+
+    pub fn reconstructs_surface(&self) -> bool {
+        self.surface.is_some() || self.show_surface
+    }
+
+    // Loose spheres with best fit insertion, so every point of the inputs is shown once.
+    pub fn octree_builder(&self, size: f32) -> OctreeBuilder {
+        let builder = OctreeBuilder::new(size)
+            .depth(self.depth)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit));
+        match self.capacity {
+            Some(capacity) => builder.division(Division::Lazy { capacity, max_depth: self.max_depth }),
+            None => builder,
+        }
+    }
+}
+
+impl SurfaceFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?;
+        if extension.eq_ignore_ascii_case("obj") {
+            Some(SurfaceFormat::Obj)
+        } else if extension.eq_ignore_ascii_case("ply") {
+            Some(SurfaceFormat::Ply)
+        } else {
+            None
+        }
+    }
+}
+
+fn is_point_cloud(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("las") || extension.eq_ignore_ascii_case("laz"))
+}
+
+fn parse_winner(name: &str) -> Option<DuplicateWinner> {
+    match name {
+        "first" => Some(DuplicateWinner::First),
+        "last" => Some(DuplicateWinner::Last),
+        "highest-intensity" => Some(DuplicateWinner::HighestIntensity),
+        "first-return" => Some(DuplicateWinner::FirstReturn),
+        _ => None,
+    }
+}
+
+fn parse_number(option: &str, value: String) -> Result<usize, ArgsError> {
+    value
+        .parse()
+        .map_err(|_| invalid_value(option, value, "a non-negative integer"))
+}
+
+fn parse_positive(option: &str, value: String) -> Result<f32, ArgsError> {
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number),
+        _ => Err(invalid_value(option, value, "a positive number")),
+    }
+}
+
+// For options where zero has a meaning of its own, no clipping or only identical positions.
+fn parse_non_negative(option: &str, value: String) -> Result<f32, ArgsError> {
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() && number >= 0.0 => Ok(number),
+        _ => Err(invalid_value(option, value, "a non-negative number")),
+    }
+}
+
+fn invalid_value(option: &str, value: String, expected: &'static str) -> ArgsError {
+    ArgsError::InvalidValue { option: option.to_string(), value, expected }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Args, ArgsError, ColorMode, TransformMode};
+
+    fn parse(arguments: &[&str]) -> Result<Args, ArgsError> {
+        Args::parse(arguments.iter().map(|argument| argument.to_string()))
+    }
+
+    #[test]
+    fn parses_options_and_inputs() {
+        let args = parse(&[
+            "a.las",
+            "--depth",
+            "4",
+            "--transform",
+            "center",
+            "--color",
+            "elevation",
+            "--point-size",
+            "1.5",
+            "--intensity-clip",
+            "0",
+            "--dedup",
+            "0",
+            "--no-edl",
+            "--",
+            "--b.laz",
+        ])
+        .unwrap();
+        assert_eq!(args.inputs, vec![PathBuf::from("a.las"), PathBuf::from("--b.laz")]);
+        assert_eq!(args.depth, 4);
+        assert_eq!(args.transform, TransformMode::Center);
+        assert_eq!(args.color, ColorMode::Elevation);
+        assert_eq!(args.splat.size, 1.5);
+        assert_eq!(args.color_settings.intensity_clip, 0.0);
+        assert_eq!(args.dedup, Some(0.0));
+        assert!(!args.edl.enabled);
+    }
+
+    #[test]
+    fn reports_missing_values() {
+        assert!(matches!(parse(&["a.las", "--depth"]), Err(ArgsError::MissingValue(option)) if option == "--depth"));
+        assert!(matches!(parse(&["--edl-radius"]), Err(ArgsError::MissingValue(option)) if option == "--edl-radius"));
+    }
+
+    #[test]
+    fn reports_unknown_options() {
+        assert!(matches!(parse(&["a.las", "--depht", "4"]), Err(ArgsError::UnknownOption(option)) if option == "--depht"));
+        assert!(matches!(parse(&["-x"]), Err(ArgsError::UnknownOption(option)) if option == "-x"));
+    }
+
+    #[test]
+    fn rejects_bad_numbers() {
+        let invalid = [
+            ["--depth", "-1"],
+            ["--point-budget", "many"],
+            ["--point-size", "0"],
+            ["--point-size", "-2"],
+            ["--edl-strength", "NaN"],
+            ["--edl-radius", "inf"],
+            ["--intensity-gamma", "0"],
+            ["--intensity-clip", "-1"],
+            ["--dedup", "inf"],
+        ];
+        for [option, value] in invalid {
+            let result = parse(&["a.las", option, value]);
+            assert!(
+                matches!(&result, Err(ArgsError::InvalidValue { option: found, value: found_value, .. }) if found == option && found_value == value),
+                "{option} {value}: {result:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+use octree::PointAttributes;
+
+use bevy::render::renderer::RenderQueue;
+
+use crate::{
+    classification::ClassVisibility,
+    lod::LodTree,
+    splat::{self, SplatMaterial, SplatPoint},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    #[default]
+    Uniform,
+    Rgb,
+    Intensity,
+    Elevation,
+    Classification,
+    ReturnNumber,
+    PointSourceId,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Ramp {
+    // Blue through green to red.
+    #[default]
+    Rainbow,
+    Viridis,
+    Grayscale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorSettings {
+    pub ramp: Ramp,
+    pub intensity_gamma: f32,
+    // Percentage of the darkest and of the brightest intensities clipped by the stretch.
+    pub intensity_clip: f32,
+}
+
+// Colour mode and the ranges of the whole scene, so the meshes of all nodes use the same scales.
+#[derive(Resource)]
+pub struct PointColors {
+    pub mode: ColorMode,
+    pub settings: ColorSettings,
+    pub elevation_range: [f32; 2],
+    pub intensity_range: [f32; 2],
+    // Largest value of 8 or 16 bit colour channels.
+    pub rgb_scale: f32,
+}
+
+const MODES: [ColorMode; 7] = [
+    ColorMode::Uniform,
+    ColorMode::Rgb,
+    ColorMode::Intensity,
+    ColorMode::Elevation,
+    ColorMode::Classification,
+    ColorMode::ReturnNumber,
+    ColorMode::PointSourceId,
+];
+
+const RAINBOW: [[f32; 3]; 3] = [[0.0, 0.0, 1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]];
+const VIRIDIS: [[f32; 3]; 5] = [
+    [0.267, 0.005, 0.329],
+    [0.229, 0.322, 0.546],
+    [0.128, 0.567, 0.551],
+    [0.369, 0.789, 0.383],
+    [0.993, 0.906, 0.144],
+];
+const GRAYSCALE: [[f32; 3]; 2] = [[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]];
+const MISSING: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+
+impl Default for ColorSettings {
+    fn default() -> Self {
+        Self {
+            ramp: Ramp::Rainbow,
+            intensity_gamma: 1.0,
+            intensity_clip: 2.0,
+        }
+    }
+}
+
+impl ColorMode {
+    pub fn next(&self) -> Self {
+        let i = MODES.iter().position(|mode| mode == self).unwrap_or(0);
+        MODES[(i + 1) % MODES.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorMode::Uniform => "uniform",
+            ColorMode::Rgb => "rgb",
+            ColorMode::Intensity => "intensity",
+            ColorMode::Elevation => "elevation",
+            ColorMode::Classification => "classification",
+            ColorMode::ReturnNumber => "return-number",
+            ColorMode::PointSourceId => "point-source",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        MODES.into_iter().find(|mode| mode.name() == name)
+    }
+}
+
+impl Ramp {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rainbow" => Some(Ramp::Rainbow),
+            "viridis" => Some(Ramp::Viridis),
+            "grayscale" => Some(Ramp::Grayscale),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self, t: f32) -> [f32; 4] {
+        let stops: &[[f32; 3]] = match self {
+            Ramp::Rainbow => &RAINBOW,
+            Ramp::Viridis => &VIRIDIS,
+            Ramp::Grayscale => &GRAYSCALE,
+        };
+
+        let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let i = (position as usize).min(stops.len() - 2);
+        let t = position - i as f32;
+        let [a, b] = [stops[i], stops[i + 1]];
+        [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t, a[2] + (b[2] - a[2]) * t, 1.0]
+    }
+}
+
+impl PointColors {
+    pub fn new(mode: ColorMode, settings: ColorSettings, points: &[[f32; 3]], attributes: &[PointAttributes]) -> Self {
+        let min = points.iter().map(|[_, y, _]| *y).fold(f32::INFINITY, f32::min);
+        let max = points.iter().map(|[_, y, _]| *y).fold(f32::NEG_INFINITY, f32::max);
+
+        Self {
+            mode,
+            settings,
+            elevation_range: [min, max],
+            intensity_range: intensity_range(attributes, settings.intensity_clip),
+            rgb_scale: rgb_scale(attributes),
+        }
+    }
+
+    // Vertex colours for the current mode, `None` leaves the material colour.
+    pub fn gen_colors(&self, points: &[[f32; 3]], attributes: &[PointAttributes]) -> Option<Vec<[f32; 4]>> {
+        match self.mode {
+            ColorMode::Uniform => {
+                None
+            }
+            ColorMode::Rgb => {
+                Some(attributes.iter().map(|attributes| rgb_color(attributes.color, self.rgb_scale)).collect())
+            }
+            ColorMode::Intensity => {
+                Some(attributes.iter().map(|attributes| self.intensity_color(attributes.intensity)).collect())
+            }
+            ColorMode::Elevation => {
+                Some(points.iter().map(|[_, y, _]| self.elevation_color(*y)).collect())
+            }
+            ColorMode::Classification => {
+                Some(attributes.iter().map(|attributes| classification_color(attributes.classification)).collect())
+            }
+            ColorMode::ReturnNumber => {
+                Some(attributes.iter().map(|attributes| return_number_color(attributes.return_number)).collect())
+            }
+            ColorMode::PointSourceId => {
+                Some(attributes.iter().map(|attributes| point_source_color(attributes.point_source_id)).collect())
+            }
+        }
+    }
+
+    // Splats of the uniform mode are white, so they show the material colour.
+    pub fn apply(&self, splats: &mut [SplatPoint], points: &[[f32; 3]], attributes: &[PointAttributes]) {
+        match self.gen_colors(points, attributes) {
+            Some(colors) => {
+                for (splat, color) in splats.iter_mut().zip(colors) {
+                    splat.color = splat::pack_color(color);
+                }
+            }
+            None => {
+                let white = splat::pack_color([1.0; 4]);
+                for splat in splats.iter_mut() {
+                    splat.color = white;
+                }
+            }
+        }
+    }
+
+    fn intensity_color(&self, intensity: u16) -> [f32; 4] {
+        let [low, high] = self.intensity_range;
+        let t = ((intensity as f32 - low) / (high - low).max(1.0)).clamp(0.0, 1.0);
+        let value = t.powf(self.settings.intensity_gamma);
+        [value, value, value, 1.0]
+    }
+
+    fn elevation_color(&self, elevation: f32) -> [f32; 4] {
+        let [min, max] = self.elevation_range;
+        self.settings.ramp.color((elevation - min) / (max - min).max(f32::EPSILON))
+    }
+}
+
+pub fn cycle_color_mode(
+    keys: Res<ButtonInput<KeyCode>>,
+    colors: Option<ResMut<PointColors>>,
+    visibility: Option<Res<ClassVisibility>>,
+    lod: Option<Res<LodTree>>,
+    materials: Res<Assets<SplatMaterial>>,
+    queue: Res<RenderQueue>,
+) {
+    let (Some(mut colors), Some(visibility), Some(lod)) = (colors, visibility, lod) else {
+        return;
+    };
+    if !keys.just_pressed(KeyCode::KeyC) {
+        return;
+    }
+
+    colors.mode = colors.mode.next();
+    println!("colour mode: {}", colors.mode.name());
+    for (node, material) in lod.loaded() {
+        if let Some(material) = materials.get(material) {
+            splat::write_splat_buffer(&queue, &material.points, &lod.gen_splats(node, &colors, &visibility));
+        }
+    }
+}
+
+// Files store either 8 or 16 bit colour in the 16 bit fields, 8 bit colour never exceeds 255.
+fn rgb_scale(attributes: &[PointAttributes]) -> f32 {
+    let max = attributes
+        .iter()
+        .filter_map(|attributes| attributes.color)
+        .flat_map(|color| color.into_iter())
+        .max()
+        .unwrap_or(0);
+    if max <= u8::MAX as u16 { u8::MAX as f32 } else { u16::MAX as f32 }
+}
+
+fn rgb_color(color: Option<[u16; 3]>, scale: f32) -> [f32; 4] {
+    match color {
+        Some([red, green, blue]) => [red as f32 / scale, green as f32 / scale, blue as f32 / scale, 1.0],
+        None => MISSING,
+    }
+}
+
+// Intensities at the clip percentiles, the stretch maps them to black and white.
+fn intensity_range(attributes: &[PointAttributes], clip: f32) -> [f32; 2] {
+    let mut histogram = vec![0usize; u16::MAX as usize + 1];
+    for attributes in attributes {
+        histogram[attributes.intensity as usize] += 1;
+    }
+
+    let clipped = (attributes.len() as f32 * clip.clamp(0.0, 50.0) / 100.0) as usize;
+    let percentile = |rank: usize| -> usize {
+        let mut seen = 0;
+        for (intensity, count) in histogram.iter().enumerate() {
+            seen += count;
+            if seen > rank {
+                return intensity;
+            }
+        }
+        u16::MAX as usize
+    };
+    [percentile(clipped) as f32, percentile(attributes.len().saturating_sub(clipped + 1)) as f32]
+}
+
+// ASPRS standard classes of LAS 1.4.
+pub fn classification_color(classification: u8) -> [f32; 4] {
+    let [red, green, blue] = match classification {
+        0 => [0.6, 0.6, 0.6],
+        1 => [0.8, 0.8, 0.8],
+        2 => [0.65, 0.45, 0.25],
+        3 => [0.6, 0.9, 0.4],
+        4 => [0.3, 0.75, 0.2],
+        5 => [0.1, 0.5, 0.1],
+        6 => [0.9, 0.3, 0.2],
+        7 => [1.0, 0.0, 1.0],
+        8 => [1.0, 1.0, 0.0],
+        9 => [0.2, 0.4, 1.0],
+        10 => [0.5, 0.3, 0.5],
+        11 => [0.4, 0.4, 0.4],
+        12 => [1.0, 0.8, 0.0],
+        13 => [0.9, 0.9, 0.5],
+        14 => [1.0, 1.0, 0.3],
+        15 => [0.7, 0.5, 0.3],
+        16 => [0.9, 0.6, 0.9],
+        17 => [0.6, 0.4, 0.3],
+        18 => [1.0, 0.0, 0.5],
+        _ => return MISSING,
+    };
+    [red, green, blue, 1.0]
+}
+
+fn return_number_color(return_number: u8) -> [f32; 4] {
+    match return_number {
+        1 => [1.0, 0.2, 0.2, 1.0],
+        2 => [0.2, 0.9, 0.2, 1.0],
+        3 => [0.2, 0.4, 1.0, 1.0],
+        4 => [1.0, 0.9, 0.2, 1.0],
+        5.. => [1.0, 0.2, 1.0, 1.0],
+        0 => MISSING,
+    }
+}
+
+// Spreads the hues of consecutive ids with the golden ratio, so neighbouring flight lines differ.
+fn point_source_color(point_source_id: u16) -> [f32; 4] {
+    let hue = (point_source_id as f32 * 0.618_034).fract() * 360.0;
+    let color = Color::hsl(hue, 0.8, 0.55).to_srgba();
+    [color.red, color.green, color.blue, 1.0]
+}
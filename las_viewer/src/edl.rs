@@ -0,0 +1,256 @@
+use bevy::{
+    core_pipeline::{
+        core_3d::graph::{Core3d, Node3d},
+        fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+    },
+    ecs::query::QueryItem,
+    prelude::*,
+    render::{
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_graph::{NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner},
+        render_resource::{
+            binding_types::{texture_2d, texture_depth_2d, uniform_buffer},
+            *,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        view::{ViewDepthTexture, ViewTarget},
+        Render, RenderApp, RenderSet,
+    },
+};
+
+const STRENGTH_STEP: f32 = 1.25;
+const RADIUS_STEP: f32 = 1.25;
+const MIN_RADIUS: f32 = 0.5;
+const MAX_RADIUS: f32 = 8.0;
+
+// Eye-dome lighting of a camera, shades pixels behind their neighbours in screen space. The
+// camera needs a depth texture that can be bound, see `depth_texture_usages`.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct EdlSettings {
+    pub enabled: bool,
+    pub strength: f32,
+    // Distance in pixels of the neighbours compared with every pixel.
+    pub radius: f32,
+}
+
+// The `ShaderType` derive emits a layout check function that is never called, the allow has to
+// cover the module around the struct to reach it.
+#[allow(dead_code)]
+mod uniform {
+    use bevy::render::render_resource::ShaderType;
+
+    // What the shader gets of the settings.
+    #[derive(Debug, Clone, Copy, ShaderType)]
+    pub struct EdlUniform {
+        pub strength: f32,
+        pub radius: f32,
+    }
+}
+
+use uniform::EdlUniform;
+
+pub struct EdlPlugin;
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct EdlLabel;
+
+#[derive(Default)]
+struct EdlNode;
+
+#[derive(Resource)]
+struct EdlPipeline {
+    layout: BindGroupLayout,
+    shader: Handle<Shader>,
+}
+
+// Pipeline of a view, for the format of its main texture.
+#[derive(Component)]
+struct EdlPipelineId(CachedRenderPipelineId);
+
+impl Default for EdlSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            strength: 1.0,
+            radius: 1.4,
+        }
+    }
+}
+
+// Only cameras with EDL enabled are extracted.
+impl ExtractComponent for EdlSettings {
+    type QueryData = &'static Self;
+    type QueryFilter = ();
+    type Out = Self;
+
+    fn extract_component(settings: QueryItem<'_, Self::QueryData>) -> Option<Self::Out> {
+        if !settings.enabled || settings.strength <= 0.0 {
+            return None;
+        }
+        Some(*settings)
+    }
+}
+
+impl Plugin for EdlPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ExtractComponentPlugin::<EdlSettings>::default());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<SpecializedRenderPipelines<EdlPipeline>>()
+            .add_systems(Render, prepare_edl_pipelines.in_set(RenderSet::Prepare))
+            .add_render_graph_node::<ViewNodeRunner<EdlNode>>(Core3d, EdlLabel)
+            .add_render_graph_edges(Core3d, (Node3d::Tonemapping, EdlLabel, Node3d::EndMainPassPostProcessing));
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<EdlPipeline>();
+    }
+}
+
+impl ViewNode for EdlNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static ViewDepthTexture,
+        &'static EdlSettings,
+        &'static EdlPipelineId,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (view_target, depth, settings, pipeline_id): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let edl_pipeline = world.resource::<EdlPipeline>();
+        let Some(pipeline) = world.resource::<PipelineCache>().get_render_pipeline(pipeline_id.0) else {
+            return Ok(());
+        };
+
+        let mut uniform = UniformBuffer::from(EdlUniform {
+            strength: settings.strength,
+            radius: settings.radius,
+        });
+        uniform.write_buffer(render_context.render_device(), world.resource::<RenderQueue>());
+        let Some(uniform) = uniform.binding() else {
+            return Ok(());
+        };
+
+        let post_process = view_target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            "edl_bind_group",
+            &edl_pipeline.layout,
+            &BindGroupEntries::sequential((post_process.source, depth.view(), uniform)),
+        );
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("edl_pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_render_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
+
+impl FromWorld for EdlPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let layout = world.resource::<RenderDevice>().create_bind_group_layout(
+            "edl_bind_group_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    texture_depth_2d(),
+                    uniform_buffer::<EdlUniform>(false),
+                ),
+            ),
+        );
+
+        Self {
+            layout,
+            shader: world.load_asset("shaders/edl.wgsl"),
+        }
+    }
+}
+
+// The key is the format of the main texture of the view, which differs with HDR.
+impl SpecializedRenderPipeline for EdlPipeline {
+    type Key = TextureFormat;
+
+    fn specialize(&self, format: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("edl_pipeline".into()),
+            layout: vec![self.layout.clone()],
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            push_constant_ranges: vec![],
+        }
+    }
+}
+
+fn prepare_edl_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<EdlPipeline>>,
+    edl_pipeline: Res<EdlPipeline>,
+    views: Query<(Entity, &ViewTarget), With<EdlSettings>>,
+) {
+    for (entity, view_target) in views.iter() {
+        let pipeline_id = pipelines.specialize(&pipeline_cache, &edl_pipeline, view_target.main_texture_format());
+        commands.entity(entity).insert(EdlPipelineId(pipeline_id));
+    }
+}
+
+// Keys: E turns EDL on and off, [ and ] change its strength, with shift its radius.
+pub fn adjust_edl(keys: Res<ButtonInput<KeyCode>>, mut cameras: Query<&mut EdlSettings>) {
+    for mut settings in cameras.iter_mut() {
+        if keys.just_pressed(KeyCode::KeyE) {
+            settings.enabled = !settings.enabled;
+            println!("eye-dome lighting: {}", if settings.enabled { "on" } else { "off" });
+        }
+        // With shift held the brackets change the radius instead of the strength.
+        let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        if keys.just_pressed(KeyCode::BracketRight) {
+            if shift {
+                settings.radius = (settings.radius * RADIUS_STEP).min(MAX_RADIUS);
+            } else {
+                settings.strength *= STRENGTH_STEP;
+            }
+        }
+        if keys.just_pressed(KeyCode::BracketLeft) {
+            if shift {
+                settings.radius = (settings.radius / RADIUS_STEP).max(MIN_RADIUS);
+            } else {
+                settings.strength /= STRENGTH_STEP;
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::{fmt, path::Path, sync::atomic::AtomicU64};
+
+use crate::TilePoints;
+
+const LASZIP_USER_ID: &str = "laszip encoded";
+const LASZIP_RECORD_ID: u16 = 22204;
+// Chunk size LASzip writes when every chunk has its own size.
+const VARIABLE_CHUNK_SIZE: u32 = u32::MAX;
+
+// Content of the LASzip VLR that describes how the points of a LAZ file are compressed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LazInfo {
+    pub compressor: u16,
+    pub coder: u16,
+    pub version: (u8, u8, u16),
+    pub options: u32,
+    pub chunk_size: u32,
+    // Type, size and version of every item of a point record.
+    pub items: Vec<(u16, u16, u16)>,
+}
+
+impl LazInfo {
+    pub fn from_header(header: &las::Header) -> Option<Self> {
+        let vlr = header
+            .vlrs()
+            .iter()
+            .find(|vlr| vlr.user_id.trim_end_matches('\0') == LASZIP_USER_ID && vlr.record_id == LASZIP_RECORD_ID)?;
+        let data = &vlr.data;
+
+        let u16_at = |offset: usize| Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?));
+        let u32_at = |offset: usize| Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?));
+
+        let item_count = u16_at(32)? as usize;
+        let items = (0..item_count)
+            .map(|i| {
+                let offset = 34 + i * 6;
+                Some((u16_at(offset)?, u16_at(offset + 2)?, u16_at(offset + 4)?))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self {
+            compressor: u16_at(0)?,
+            coder: u16_at(2)?,
+            version: (*data.get(4)?, *data.get(5)?, u16_at(6)?),
+            options: u32_at(8)?,
+            chunk_size: u32_at(12)?,
+            items,
+        })
+    }
+
+    // Points are compressed in chunks of a fixed size, which can be decompressed independently.
+    pub fn has_fixed_chunks(&self) -> bool {
+        matches!(self.compressor, 2 | 3) && self.chunk_size > 0 && self.chunk_size != VARIABLE_CHUNK_SIZE
+    }
+
+    fn compressor_name(&self) -> &'static str {
+        match self.compressor {
+            0 => "none",
+            1 => "pointwise",
+            2 => "pointwise chunked",
+            3 => "layered chunked",
+            _ => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for LazInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (major, minor, revision) = self.version;
+        writeln!(f, "LAZ compression:")?;
+        writeln!(f, "    laszip version: {major}.{minor}r{revision}")?;
+        writeln!(f, "    compressor: {} ({})", self.compressor_name(), self.compressor)?;
+        writeln!(f, "    coder: {}", self.coder)?;
+        writeln!(f, "    options: {:#x}", self.options)?;
+        if self.chunk_size == VARIABLE_CHUNK_SIZE {
+            writeln!(f, "    chunk size: variable")?;
+        } else {
+            writeln!(f, "    chunk size: {} points", self.chunk_size)?;
+        }
+        for (item_type, size, version) in self.items.iter() {
+            writeln!(f, "    item: type {item_type}, {size} bytes, version {version}")?;
+        }
+        Ok(())
+    }
+}
+
+// Reads the points of a LAZ file with fixed size chunks on several threads. Every thread opens
+// its own reader and seeks to the first chunk of its range, ranges start on chunk boundaries.
+pub fn read_points_parallel(
+    path: &Path,
+    info: &LazInfo,
+    point_count: u64,
+    bounds: &las::Bounds,
+    progress: &AtomicU64,
+) -> las::Result<TilePoints> {
+    let chunk_size = info.chunk_size as u64;
+    let chunk_count = point_count.div_ceil(chunk_size);
+    let threads = std::thread::available_parallelism()
+        .map(|threads| threads.get() as u64)
+        .unwrap_or(1)
+        .min(chunk_count)
+        .max(1);
+    let chunks_per_thread = chunk_count.div_ceil(threads);
+
+    let parts: Vec<las::Result<TilePoints>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|thread| {
+                let start = (thread * chunks_per_thread * chunk_size).min(point_count);
+                let end = ((thread + 1) * chunks_per_thread * chunk_size).min(point_count);
+                scope.spawn(move || {
+                    let mut reader = las::Reader::from_path(path)?;
+                    las::Read::seek(&mut reader, start)?;
+                    crate::read_points(&mut reader, end - start, bounds, progress)
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("point reading thread panicked"))
+            .collect()
+    });
+
+    let mut points = Vec::with_capacity(point_count as usize);
+    let mut attributes = Vec::with_capacity(point_count as usize);
+    for part in parts {
+        let (part_points, part_attributes) = part?;
+        points.extend(part_points);
+        attributes.extend(part_attributes);
+    }
+    Ok((points, attributes))
+}
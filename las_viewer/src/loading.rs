@@ -0,0 +1,423 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc::{self, Receiver, Sender, TryRecvError},
+    Arc, Mutex,
+};
+
+use bevy::{
+    prelude::*,
+    render::{renderer::RenderDevice, view::NoFrustumCulling},
+    tasks::{AsyncComputeTaskPool, Task},
+};
+use bevy_panorbit_camera::PanOrbitCamera;
+
+use crate::{
+    classification::{self, ClassVisibility},
+    cli::Args,
+    color::PointColors,
+    lod::LodTree,
+    splat::{self, SplatMaterial, SplatQuads, SplatSettings},
+    TilePoints,
+};
+
+const BAR_WIDTH: f32 = 200.0;
+const BAR_HEIGHT: f32 = 8.0;
+const PANEL_FONT_SIZE: f32 = 16.0;
+const BAR_COLOR: Color = Color::srgb(0.3, 0.7, 1.0);
+const ERROR_COLOR: Color = Color::srgb(1.0, 0.4, 0.4);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadStage {
+    // Points read from the inputs or the cache.
+    Read,
+    // Tiles imported into the octree, then duplicate removal when enabled, levels of detail and
+    // colours.
+    Index,
+    // Meshes of the nodes the camera needs.
+    Upload,
+}
+
+// Shared with the loading task, which counts what it has done of every stage.
+#[derive(Default)]
+pub struct LoadCounters {
+    pub read: AtomicU64,
+    pub read_total: AtomicU64,
+    pub index: AtomicU64,
+    pub index_total: AtomicU64,
+}
+
+#[derive(Resource, Clone, Default)]
+pub struct LoadProgress(pub Arc<LoadCounters>);
+
+pub struct LoadedScene {
+    pub lod: LodTree,
+    pub colors: PointColors,
+    pub visibility: ClassVisibility,
+    pub surface: Option<Mesh>,
+}
+
+// Where the scene lies, known once the headers are read.
+struct SceneFrame {
+    // Cells of the empty octree.
+    lines: Mesh,
+    focus: Vec3,
+    size: f32,
+    // Spacing of the point budget spread over the scene, which previews roughly keep.
+    preview_spacing: f32,
+}
+
+enum LoadEvent {
+    Framed(SceneFrame),
+    // Every `preview_stride`th point of a tile that was read, in the scene frame.
+    Tile(TilePoints),
+    Finished(Box<LoadedScene>),
+    // Loading stopped, the message says why.
+    Failed(String),
+}
+
+// Lives while the loading task runs.
+#[derive(Resource)]
+pub struct Loading {
+    events: Mutex<Receiver<LoadEvent>>,
+    // Spacing of the preview splats, set when the scene is framed.
+    preview_spacing: f32,
+    _task: Task<()>,
+}
+
+// Drawn from the tiles as they are read, until the levels of detail are shown.
+#[derive(Component)]
+pub struct Preview;
+
+#[derive(Component)]
+pub struct ProgressBar(LoadStage);
+
+#[derive(Component)]
+pub struct ProgressLabel(LoadStage);
+
+// Shows why loading stopped, empty while it runs.
+#[derive(Component)]
+pub struct ProgressMessage;
+
+impl LoadStage {
+    const ALL: [LoadStage; 3] = [LoadStage::Read, LoadStage::Index, LoadStage::Upload];
+
+    fn name(&self) -> &'static str {
+        match self {
+            LoadStage::Read => "read",
+            LoadStage::Index => "index",
+            LoadStage::Upload => "upload",
+        }
+    }
+
+    fn unit(&self) -> &'static str {
+        match self {
+            LoadStage::Read => "points",
+            LoadStage::Index => "steps",
+            LoadStage::Upload => "nodes",
+        }
+    }
+}
+
+impl Loading {
+    // Reads the headers and then the points of the inputs on the async compute pool. Previews
+    // keep about `args.point_budget` points of the whole scene.
+    pub fn start(args: &Args, progress: &LoadProgress) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let counters = progress.0.clone();
+        // Every tile is one step, then duplicate removal when enabled, the levels of detail and
+        // colours.
+        let steps = args.inputs.len() + args.dedup.is_some() as usize + 2;
+        counters.index_total.store(steps as u64, Ordering::Relaxed);
+
+        let args = args.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move {
+            if let Err(error) = load_scene(args, &counters, &sender) {
+                let _ = sender.send(LoadEvent::Failed(error));
+            }
+        });
+
+        Self {
+            events: Mutex::new(receiver),
+            preview_spacing: 0.0,
+            _task: task,
+        }
+    }
+}
+
+// Sends the tiles and the finished scene, an error stops loading.
+fn load_scene(args: Args, counters: &LoadCounters, sender: &Sender<LoadEvent>) -> Result<(), String> {
+    let (mut tree, bounds, point_count, size) = crate::build_scene(&args)?;
+    counters.read_total.store(point_count, Ordering::Relaxed);
+    let preview_stride = point_count.div_ceil(args.point_budget.max(1) as u64).max(1) as usize;
+
+    let [tree_min, tree_max] = tree.bounds();
+    let frame = SceneFrame {
+        lines: crate::gen_debug_lines(&tree),
+        focus: Vec3::from(tree_min).lerp(Vec3::from(tree_max), 0.5),
+        size,
+        preview_spacing: size / (args.point_budget.max(1) as f32).sqrt(),
+    };
+    // The viewer may have been closed, then nobody waits for the rest.
+    if sender.send(LoadEvent::Framed(frame)).is_err() {
+        return Ok(());
+    }
+
+    for (i, path) in args.inputs.iter().enumerate() {
+        let ((mut points, attributes), header) = crate::read_las(path, &args.cache_dir, &counters.read, args.verbose)
+            .map_err(|error| format!("failed to read {}: {error}", path.display()))?;
+        crate::transform(&mut points, &header.bounds(), &bounds, args.transform);
+
+        let preview = (
+            points.iter().step_by(preview_stride).copied().collect(),
+            attributes.iter().step_by(preview_stride).copied().collect(),
+        );
+        // The viewer may have been closed, then nobody waits for the rest.
+        if sender.send(LoadEvent::Tile(preview)).is_err() {
+            return Ok(());
+        }
+
+        println!("importing tile {}/{} ({} points) to octree...", i + 1, args.inputs.len(), points.len());
+        tree.import_with_attributes(&points, &attributes)
+            .map_err(|error| format!("failed to import {}: {error}", path.display()))?;
+        counters.index.fetch_add(1, Ordering::Relaxed);
+    }
+
+    if let Some(tolerance) = args.dedup {
+        println!("removing duplicate points...");
+        let removed = tree.dedup(tolerance, args.dedup_winner);
+        println!("removed {} duplicate points", removed);
+        counters.index.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let mut surface = None;
+    if args.reconstructs_surface() {
+        println!("reconstructing surface...");
+        let mesh = octree::reconstruction::reconstruct(
+            &tree,
+            &octree::reconstruction::ReconstructionParams::default(),
+        )
+        .map_err(|error| format!("failed to reconstruct the surface: {error}"))?;
+        if let Some(path) = args.surface.as_ref() {
+            println!("writing surface to {}...", path.display());
+            crate::write_surface(&mesh, path)
+                .map_err(|error| format!("failed to write the surface to {}: {error}", path.display()))?;
+        }
+        if args.show_surface {
+            surface = Some(crate::gen_surface_mesh(&mesh));
+        }
+    }
+
+    println!("building levels of detail...");
+    let lod = LodTree::build(tree, args.point_budget);
+    println!("{} level of detail nodes, {} levels", lod.nodes.len(), lod.spacings.len());
+    counters.index.fetch_add(1, Ordering::Relaxed);
+
+    let colors = PointColors::new(args.color, args.color_settings, &lod.points, &lod.attributes);
+    let visibility = ClassVisibility::from_attributes(&lod.attributes);
+    counters.index.fetch_add(1, Ordering::Relaxed);
+
+    let _ = sender.send(LoadEvent::Finished(Box::new(LoadedScene { lod, colors, visibility, surface })));
+    Ok(())
+}
+
+// Shows the tiles read so far and takes over the scene once the task has finished.
+#[allow(clippy::too_many_arguments)]
+pub fn receive_loaded(
+    mut commands: Commands,
+    loading: Option<ResMut<Loading>>,
+    lod: Option<Res<LodTree>>,
+    previews: Query<Entity, With<Preview>>,
+    mut messages: Query<&mut Text, With<ProgressMessage>>,
+    lines: Query<&Handle<Mesh>, With<crate::Shape>>,
+    mut cameras: Query<(&mut Transform, &mut PanOrbitCamera)>,
+    args: Res<Args>,
+    settings: Res<SplatSettings>,
+    device: Res<RenderDevice>,
+    mut quads: ResMut<SplatQuads>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut splat_materials: ResMut<Assets<SplatMaterial>>,
+) {
+    // Previews stay until the levels of detail have replaced them.
+    if let Some(lod) = lod {
+        if lod.wanted == Some(lod.shown) {
+            for entity in previews.iter() {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+
+    let Some(mut loading) = loading else {
+        return;
+    };
+    let mut events = Vec::new();
+    {
+        let receiver = loading.events.lock().unwrap();
+        loop {
+            match receiver.try_recv() {
+                Ok(event) => events.push(event),
+                Err(TryRecvError::Empty) => break,
+                // The task ended without a result, it panicked.
+                Err(TryRecvError::Disconnected) => {
+                    events.push(LoadEvent::Failed("loading stopped unexpectedly".to_string()));
+                    break;
+                }
+            }
+        }
+    }
+    for event in events {
+        match event {
+            LoadEvent::Framed(frame) => {
+                let SceneFrame { lines: cells, focus, size, preview_spacing } = frame;
+                for handle in lines.iter() {
+                    if let Some(mesh) = meshes.get_mut(handle) {
+                        *mesh = cells.clone();
+                    }
+                }
+                for (mut transform, mut orbit) in cameras.iter_mut() {
+                    *transform = Transform::from_translation(focus + Vec3::new(-size, size, -size) / 2.0)
+                        .looking_at(focus, Vec3::Y);
+                    // A new controller takes its yaw, pitch and radius from the transform.
+                    *orbit = PanOrbitCamera { focus, ..default() };
+                }
+                loading.preview_spacing = preview_spacing;
+            }
+            LoadEvent::Tile((points, attributes)) => {
+                if points.is_empty() {
+                    continue;
+                }
+                // Coloured with the ranges of their own tile, the scene ranges are not known yet.
+                let mut splats = splat::gen_splat_points(&points);
+                PointColors::new(args.color, args.color_settings, &points, &attributes).apply(&mut splats, &points, &attributes);
+                let splats = splat::gen_splat_buffer(&device, &splats);
+                commands.spawn((
+                    MaterialMeshBundle {
+                        mesh: quads.mesh(points.len(), &mut meshes),
+                        material: splat_materials.add(SplatMaterial::new(&settings, loading.preview_spacing, splats)),
+                        ..default()
+                    },
+                    // The shared index meshes have no extent, previews are few and never culled.
+                    NoFrustumCulling,
+                    Preview,
+                ));
+            }
+            LoadEvent::Finished(scene) => {
+                let LoadedScene { lod, colors, visibility, surface } = *scene;
+
+                if let Some(surface) = surface {
+                    commands.spawn(PbrBundle {
+                        mesh: meshes.add(surface),
+                        material: materials.add(StandardMaterial {
+                            base_color: Color::srgb(0.8, 0.8, 0.8),
+                            cull_mode: None,
+                            double_sided: true,
+                            ..default()
+                        }),
+                        ..default()
+                    });
+                    commands.spawn(DirectionalLightBundle {
+                        transform: Transform::from_xyz(0.0, 1.0, 0.0).looking_at(Vec3::new(0.3, 0.0, 0.2), Vec3::Y),
+                        ..default()
+                    });
+                }
+
+                classification::spawn_panel(&mut commands, &visibility);
+                commands.insert_resource(colors);
+                commands.insert_resource(visibility);
+                commands.insert_resource(lod);
+                commands.remove_resource::<Loading>();
+                return;
+            }
+            LoadEvent::Failed(error) => {
+                eprintln!("error: {error}");
+                for mut text in messages.iter_mut() {
+                    text.sections[0].value = format!("error: {error}");
+                }
+                commands.remove_resource::<Loading>();
+                return;
+            }
+        }
+    }
+}
+
+// One bar per stage in the bottom left corner.
+pub fn spawn_progress_panel(commands: &mut Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(8.0),
+                left: Val::Px(8.0),
+                flex_direction: FlexDirection::Column,
+                padding: UiRect::all(Val::Px(6.0)),
+                row_gap: Val::Px(4.0),
+                ..default()
+            },
+            background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .with_children(|panel| {
+            for stage in LoadStage::ALL {
+                panel.spawn((
+                    TextBundle::from_section(
+                        stage.name(),
+                        TextStyle { font_size: PANEL_FONT_SIZE, color: Color::srgb(0.9, 0.9, 0.9), ..default() },
+                    ),
+                    ProgressLabel(stage),
+                ));
+                panel
+                    .spawn(NodeBundle {
+                        style: Style { width: Val::Px(BAR_WIDTH), height: Val::Px(BAR_HEIGHT), ..default() },
+                        background_color: Color::srgb(0.2, 0.2, 0.2).into(),
+                        ..default()
+                    })
+                    .with_children(|bar| {
+                        bar.spawn((
+                            NodeBundle {
+                                style: Style { width: Val::Percent(0.0), height: Val::Percent(100.0), ..default() },
+                                background_color: BAR_COLOR.into(),
+                                ..default()
+                            },
+                            ProgressBar(stage),
+                        ));
+                    });
+            }
+            panel.spawn((
+                TextBundle::from_section("", TextStyle { font_size: PANEL_FONT_SIZE, color: ERROR_COLOR, ..default() }),
+                ProgressMessage,
+            ));
+        });
+}
+
+pub fn update_progress(
+    progress: Res<LoadProgress>,
+    lod: Option<Res<LodTree>>,
+    mut bars: Query<(&mut Style, &ProgressBar)>,
+    mut labels: Query<(&mut Text, &ProgressLabel)>,
+) {
+    let counters = &progress.0;
+    let stage_progress = |stage: LoadStage| -> (u64, u64) {
+        match stage {
+            LoadStage::Read => {
+                (counters.read.load(Ordering::Relaxed), counters.read_total.load(Ordering::Relaxed))
+            }
+            LoadStage::Index => {
+                (counters.index.load(Ordering::Relaxed), counters.index_total.load(Ordering::Relaxed))
+            }
+            LoadStage::Upload => {
+                let (shown, wanted) = lod.as_ref().map_or((0, None), |lod| (lod.shown, lod.wanted));
+                (shown as u64, wanted.unwrap_or(0) as u64)
+            }
+        }
+    };
+
+    for (mut style, bar) in bars.iter_mut() {
+        let (done, total) = stage_progress(bar.0);
+        let fraction = if total == 0 { 0.0 } else { (done as f32 / total as f32).min(1.0) };
+        style.width = Val::Percent(fraction * 100.0);
+    }
+    for (mut text, label) in labels.iter_mut() {
+        let (done, total) = stage_progress(label.0);
+        text.sections[0].value = format!("{}: {done}/{total} {}", label.0.name(), label.0.unit());
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::{BinaryHeap, HashSet},
+    ops::Range,
+};
+
+use bevy::{
+    math::Affine3A,
+    prelude::*,
+    render::{
+        primitives::{Aabb, Frustum},
+        renderer::RenderDevice,
+    },
+};
+use octree::{Octree, OctreeNode, PointAttributes};
+
+use crate::{
+    classification::ClassVisibility,
+    color::PointColors,
+    splat::{self, SplatMaterial, SplatPoint, SplatQuads, SplatSettings},
+};
+
+// Cells per edge of the grid a node samples its points on, it keeps one point of every cell.
+const SAMPLE_GRID: f32 = 128.0;
+// Leaves with more points are split into octants until they fit.
+const MAX_LEAF_POINTS: usize = 20_000;
+const MAX_DEPTH: usize = 24;
+// Children of a node are shown while its point spacing covers more pixels than this.
+const MAX_PIXEL_SPACING: f32 = 1.5;
+// Meshes created per frame, so moving the camera does not stall on uploads.
+const MAX_LOADS_PER_FRAME: usize = 16;
+
+// Nodes are additive, a node holds a sample of its subtree and its children the remaining
+// points, so a node and all its ancestors together show every point of its cell at its spacing.
+pub struct LodNode {
+    // Bounds of the points of the node and of all its descendants.
+    pub bounds: [[f32; 3]; 2],
+    pub depth: usize,
+    // Points of the node in `LodTree::points` and `LodTree::attributes`.
+    pub range: Range<usize>,
+    pub children: Vec<usize>,
+    // Entity and material, which holds the points, while the node is shown.
+    pub loaded: Option<(Entity, Handle<SplatMaterial>)>,
+}
+
+#[derive(Resource)]
+pub struct LodTree {
+    pub nodes: Vec<LodNode>,
+    // Points of every node, grouped by node.
+    pub points: Vec<[f32; 3]>,
+    pub attributes: Vec<PointAttributes>,
+    pub root: Option<usize>,
+    // Distance between the sampled points of the nodes of every depth.
+    pub spacings: Vec<f32>,
+    // Most points shown at once.
+    pub budget: usize,
+    // Nodes with points that are shown, of the ones selected for the camera. `wanted` is `None`
+    // until the first selection.
+    pub shown: usize,
+    pub wanted: Option<usize>,
+}
+
+struct BuildNode {
+    bounds: [[f32; 3]; 2],
+    depth: usize,
+    indices: Vec<u32>,
+    children: Vec<usize>,
+}
+
+// Octree nodes without their points, kept while the points are moved out of the tree.
+struct OctreeShape {
+    bounds: [[f32; 3]; 2],
+    count: usize,
+    children: Vec<OctreeShape>,
+}
+
+struct Builder<'a> {
+    points: &'a [[f32; 3]],
+    nodes: Vec<BuildNode>,
+    next_point: usize,
+}
+
+impl LodTree {
+    // Consumes the tree, its points are moved out and reordered in place, so they are never held
+    // twice.
+    pub fn build(tree: Octree, budget: usize) -> Self {
+        let [min, max] = tree.bounds();
+        let shape = OctreeShape::of(&tree.root);
+        let (mut points, mut attributes) = tree.into_points_with_attributes();
+        attributes.resize(points.len(), PointAttributes::default());
+
+        let mut builder = Builder { points: &points, nodes: Vec::new(), next_point: 0 };
+        let root = builder.add_octree_node(&shape, 0);
+
+        let max_depth = builder.nodes.iter().map(|node| node.depth).max().unwrap_or(0);
+        let spacings = (0..=max_depth)
+            .map(|depth| (max[0] - min[0]) / 2f32.powi(depth as i32) / SAMPLE_GRID)
+            .collect();
+
+        // Where every point goes, the points of a node follow each other.
+        let mut destinations = vec![0u32; points.len()];
+        let mut next = 0;
+        let nodes = builder
+            .nodes
+            .into_iter()
+            .map(|node| {
+                let start = next;
+                for i in node.indices {
+                    destinations[i as usize] = next as u32;
+                    next += 1;
+                }
+                LodNode {
+                    bounds: node.bounds,
+                    depth: node.depth,
+                    range: start..next,
+                    children: node.children,
+                    loaded: None,
+                }
+            })
+            .collect();
+
+        // Swaps every point into place, each swap puts at least one point where it belongs.
+        for i in 0..destinations.len() {
+            while destinations[i] as usize != i {
+                let destination = destinations[i] as usize;
+                points.swap(i, destination);
+                attributes.swap(i, destination);
+                destinations.swap(i, destination);
+            }
+        }
+
+        Self {
+            nodes,
+            points,
+            attributes,
+            root,
+            spacings,
+            budget,
+            shown: 0,
+            wanted: None,
+        }
+    }
+
+    pub fn gen_splats(&self, node: &LodNode, colors: &PointColors, visibility: &ClassVisibility) -> Vec<SplatPoint> {
+        let (points, attributes) = (self.node_points(node), self.node_attributes(node));
+        let mut splats = splat::gen_splat_points(points);
+        colors.apply(&mut splats, points, attributes);
+        visibility.apply(&mut splats, attributes);
+        splats
+    }
+
+    pub fn node_points(&self, node: &LodNode) -> &[[f32; 3]] {
+        &self.points[node.range.clone()]
+    }
+
+    pub fn node_attributes(&self, node: &LodNode) -> &[PointAttributes] {
+        &self.attributes[node.range.clone()]
+    }
+
+    pub fn loaded(&self) -> impl Iterator<Item = (&LodNode, &Handle<SplatMaterial>)> + '_ {
+        self.nodes
+            .iter()
+            .filter_map(|node| node.loaded.as_ref().map(|(_, material)| (node, material)))
+    }
+
+    // Nodes to show, in the order they should be loaded. Nodes are refined by their spacing on
+    // screen, the most coarse first, until the point budget is used up.
+    pub fn select(&self, frustum: &Frustum, eye: Vec3, pixels_per_unit: f32) -> Vec<usize> {
+        let mut selected = Vec::new();
+        let Some(root) = self.root else {
+            return selected;
+        };
+
+        // Priorities are positive, so the order of their bits is the order of the floats.
+        let mut queue = BinaryHeap::from([(f32::INFINITY.to_bits(), root)]);
+        let mut points = 0;
+        while let Some((_, index)) = queue.pop() {
+            let node = &self.nodes[index];
+            let [min, max] = node.bounds;
+            let aabb = Aabb::from_min_max(Vec3::from(min), Vec3::from(max));
+            if !frustum.intersects_obb(&aabb, &Affine3A::IDENTITY, true, true) {
+                continue;
+            }
+            if points + node.range.len() > self.budget {
+                break;
+            }
+            points += node.range.len();
+            selected.push(index);
+
+            if self.pixel_spacing(node, eye, pixels_per_unit) > MAX_PIXEL_SPACING {
+                for &child in node.children.iter() {
+                    let priority = self.pixel_spacing(&self.nodes[child], eye, pixels_per_unit);
+                    queue.push((priority.to_bits(), child));
+                }
+            }
+        }
+        selected
+    }
+
+    fn pixel_spacing(&self, node: &LodNode, eye: Vec3, pixels_per_unit: f32) -> f32 {
+        let [min, max] = node.bounds;
+        let closest = eye.clamp(Vec3::from(min), Vec3::from(max));
+        let distance = eye.distance(closest).max(f32::EPSILON);
+        self.spacings[node.depth] * pixels_per_unit / distance
+    }
+}
+
+impl OctreeShape {
+    fn of(node: &OctreeNode) -> Self {
+        Self {
+            bounds: node.bounds(),
+            count: node.data_points().len(),
+            children: node.nodes_ref().map_or(Vec::new(), |nodes| nodes.iter().map(OctreeShape::of).collect()),
+        }
+    }
+}
+
+impl Builder<'_> {
+    // Octree nodes hold their points in the order of `Octree::into_points_with_attributes`, own
+    // points before children.
+    fn add_octree_node(&mut self, node: &OctreeShape, depth: usize) -> Option<usize> {
+        let own: Vec<u32> = (self.next_point..self.next_point + node.count).map(|i| i as u32).collect();
+        self.next_point += node.count;
+
+        let children: Vec<usize> =
+            node.children.iter().filter_map(|child| self.add_octree_node(child, depth + 1)).collect();
+        if children.is_empty() {
+            return self.add_points(node.bounds, depth, own);
+        }
+        self.add_parent(node.bounds, depth, own, children)
+    }
+
+    // Splits the points of a cell into octants until every leaf is small enough.
+    fn add_points(&mut self, cell: [[f32; 3]; 2], depth: usize, indices: Vec<u32>) -> Option<usize> {
+        if indices.is_empty() {
+            return None;
+        }
+        if indices.len() <= MAX_LEAF_POINTS || depth >= MAX_DEPTH {
+            let bounds = self.points_bounds(&indices);
+            self.nodes.push(BuildNode { bounds, depth, indices, children: Vec::new() });
+            return Some(self.nodes.len() - 1);
+        }
+
+        let [min, max] = cell;
+        let middle = [0, 1, 2].map(|axis| (min[axis] + max[axis]) / 2.0);
+        let mut octants: [Vec<u32>; 8] = Default::default();
+        for i in indices {
+            let point = self.points[i as usize];
+            let octant = (0..3).filter(|axis| point[*axis] >= middle[*axis]).map(|axis| 1 << axis).sum::<usize>();
+            octants[octant].push(i);
+        }
+
+        let children = octants
+            .into_iter()
+            .enumerate()
+            .filter_map(|(octant, indices)| {
+                let octant_min = [0, 1, 2].map(|axis| if octant & (1 << axis) == 0 { min[axis] } else { middle[axis] });
+                let octant_max = [0, 1, 2].map(|axis| if octant & (1 << axis) == 0 { middle[axis] } else { max[axis] });
+                self.add_points([octant_min, octant_max], depth + 1, indices)
+            })
+            .collect();
+        self.add_parent(cell, depth, Vec::new(), children)
+    }
+
+    // Moves the first point of every grid cell up from the children, the rest stays with them.
+    fn add_parent(&mut self, cell: [[f32; 3]; 2], depth: usize, own: Vec<u32>, children: Vec<usize>) -> Option<usize> {
+        let points = self.points;
+        let [min, max] = cell;
+        let cell_size = ((max[0] - min[0]) / SAMPLE_GRID).max(f32::EPSILON);
+        let grid_cell = |i: &u32| [0, 1, 2].map(|axis| ((points[*i as usize][axis] - min[axis]) / cell_size).floor() as i32);
+
+        let mut taken = HashSet::new();
+        let mut indices = Vec::new();
+        for &child in children.iter() {
+            let child_indices = std::mem::take(&mut self.nodes[child].indices);
+            let (sampled, remaining): (Vec<u32>, Vec<u32>) =
+                child_indices.into_iter().partition(|i| taken.insert(grid_cell(i)));
+            indices.extend(sampled);
+            self.nodes[child].indices = remaining;
+        }
+
+        let mut bounds = self.points_bounds(&own);
+        for &child in children.iter() {
+            let [child_min, child_max] = self.nodes[child].bounds;
+            for axis in 0..3 {
+                bounds[0][axis] = bounds[0][axis].min(child_min[axis]);
+                bounds[1][axis] = bounds[1][axis].max(child_max[axis]);
+            }
+        }
+
+        // Own points have no children to go to, they are all kept.
+        indices.extend(own);
+        self.nodes.push(BuildNode { bounds, depth, indices, children });
+        Some(self.nodes.len() - 1)
+    }
+
+    // Empty bounds, with min above max, when there are no points.
+    fn points_bounds(&self, indices: &[u32]) -> [[f32; 3]; 2] {
+        let mut bounds = [[f32::INFINITY; 3], [f32::NEG_INFINITY; 3]];
+        for i in indices {
+            let point = self.points[*i as usize];
+            for axis in 0..3 {
+                bounds[0][axis] = bounds[0][axis].min(point[axis]);
+                bounds[1][axis] = bounds[1][axis].max(point[axis]);
+            }
+        }
+        bounds
+    }
+}
+
+// Shows the nodes selected for the camera, creating meshes of new nodes and dropping the others
+// once every selected node is shown, so moving the camera never leaves holes.
+#[allow(clippy::too_many_arguments)]
+pub fn update_lod(
+    mut commands: Commands,
+    cameras: Query<(&Camera, &GlobalTransform, &Frustum, &Projection)>,
+    lod: Option<ResMut<LodTree>>,
+    colors: Option<Res<PointColors>>,
+    visibility: Option<Res<ClassVisibility>>,
+    settings: Res<SplatSettings>,
+    device: Res<RenderDevice>,
+    mut quads: ResMut<SplatQuads>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<SplatMaterial>>,
+) {
+    let (Some(mut lod), Some(colors), Some(visibility)) = (lod, colors, visibility) else {
+        return;
+    };
+    let Ok((camera, transform, frustum, projection)) = cameras.get_single() else {
+        return;
+    };
+    let Some(viewport) = camera.physical_viewport_size() else {
+        return;
+    };
+    let pixels_per_unit = match projection {
+        Projection::Perspective(perspective) => viewport.y as f32 / (2.0 * (perspective.fov / 2.0).tan()),
+        Projection::Orthographic(orthographic) => viewport.y as f32 / orthographic.area.height(),
+    };
+
+    let selected = lod.select(frustum, transform.translation(), pixels_per_unit);
+    let mut loads = 0;
+    lod.wanted = Some(selected.iter().filter(|index| !lod.nodes[**index].range.is_empty()).count());
+    for index in selected.iter().copied() {
+        let node = &lod.nodes[index];
+        if node.loaded.is_some() || node.range.is_empty() {
+            continue;
+        }
+        if loads == MAX_LOADS_PER_FRAME {
+            break;
+        }
+
+        // Attenuated splats scale with the spacing of the depth of their node.
+        let splats = lod.gen_splats(node, &colors, &visibility);
+        let points = splat::gen_splat_buffer(&device, &splats);
+        let material = materials.add(SplatMaterial::new(&settings, lod.spacings[node.depth], points));
+        let [min, max] = node.bounds;
+        let entity = commands
+            .spawn((
+                MaterialMeshBundle {
+                    mesh: quads.mesh(splats.len(), &mut meshes),
+                    material: material.clone(),
+                    ..default()
+                },
+                // The shared index meshes have no extent, culling uses the bounds of the node.
+                Aabb::from_min_max(Vec3::from(min), Vec3::from(max)),
+            ))
+            .id();
+        lod.nodes[index].loaded = Some((entity, material));
+        loads += 1;
+    }
+    lod.shown = selected.iter().filter(|index| lod.nodes[**index].loaded.is_some()).count();
+
+    // Nodes are additive, showing old nodes next to new ones only adds points until the
+    // selection is complete.
+    if lod.wanted != Some(lod.shown) {
+        return;
+    }
+    let mut wanted = vec![false; lod.nodes.len()];
+    for &index in selected.iter() {
+        wanted[index] = true;
+    }
+    // Dropping the last handle of a material frees it and its points.
+    for (node, wanted) in lod.nodes.iter_mut().zip(wanted) {
+        if !wanted {
+            if let Some((entity, _)) = node.loaded.take() {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
@@ -1,4 +1,19 @@
-use std::{collections::VecDeque, f32::consts::PI};
+mod cache;
+mod cli;
+mod classification;
+mod color;
+mod edl;
+mod laz;
+mod loading;
+mod lod;
+mod splat;
+
+use std::{
+    collections::VecDeque,
+    f32::consts::PI,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use bevy::{
     pbr::{
@@ -11,13 +26,23 @@ use bevy::{
         render_asset::RenderAssetUsages,
         render_resource::{
             AsBindGroup, Extent3d, PolygonMode, RenderPipelineDescriptor, ShaderRef,
-            SpecializedMeshPipelineError, TextureDimension, TextureFormat,
+            SpecializedMeshPipelineError, TextureDimension, TextureFormat, TextureUsages,
         },
     },
 };
 use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
+use cli::{Args, ArgsError, CacheCommand, SurfaceFormat, TransformMode};
+use loading::{LoadProgress, Loading};
+use octree::PointAttributes;
+use splat::{SplatMaterial, SplatQuads};
+
+// Points read between updates of the read progress.
+const PROGRESS_STEP: usize = 1 << 16;
+// Smallest octree edge, scenes without extent would otherwise get an empty tree.
+const MIN_SCENE_SIZE: f32 = 1.0;
 
-const CACHE_PATH: &str = "./cached_points";
+// Positions and attributes of the points of one tile, in the same order.
+type TilePoints = (Vec<[f32; 3]>, Vec<PointAttributes>);
 
 #[derive(Asset, TypePath, Default, AsBindGroup, Debug, Clone)]
 struct LineMaterial {
@@ -26,17 +51,73 @@ struct LineMaterial {
 }
 
 fn main() {
+    let args = match Args::from_env() {
+        Ok(args) => args,
+        Err(ArgsError::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(error) => {
+            eprintln!("error: {error}");
+            eprintln!("run with --help for usage");
+            std::process::exit(2);
+        }
+    };
+
+    if let Some(command) = args.cache_command {
+        let result = match command {
+            CacheCommand::Clear => cache::clear(&args.cache_dir),
+            CacheCommand::Prune => cache::prune(&args.cache_dir),
+        };
+        match result {
+            Ok(removed) => println!("removed {} cache files from {}", removed, args.cache_dir.display()),
+            Err(error) => {
+                eprintln!("error: failed to update the cache in {}: {error}", args.cache_dir.display());
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     App::new()
+        .insert_resource(args.splat)
+        .init_resource::<SplatQuads>()
+        .insert_resource(args)
+        // MSAA is off for every camera, not only those with eye-dome lighting, which binds the
+        // depth texture as a plain `texture_depth_2d` that a multisampled one is not. Splat and
+        // line edges are aliased as a result.
+        .insert_resource(Msaa::Off)
         .add_plugins((
             DefaultPlugins.set(ImagePlugin::default_nearest()),
             WireframePlugin,
             PanOrbitCameraPlugin,
             MaterialPlugin::<LineMaterial>::default(),
+            // Splats pull their points from a storage buffer, the prepass and shadow shaders of
+            // the engine would read mesh vertices.
+            MaterialPlugin::<SplatMaterial> {
+                prepass_enabled: false,
+                shadows_enabled: false,
+                ..default()
+            },
+            edl::EdlPlugin,
         ))
         .add_systems(Startup, setup)
+        .add_systems(
+            Update,
+            (
+                loading::receive_loaded,
+                loading::update_progress,
+                color::cycle_color_mode,
+                classification::toggle_classes,
+                splat::adjust_splats,
+                edl::adjust_edl,
+                lod::update_lod,
+            ),
+        )
         .run();
 }
 
+// Lines of the octree cells.
 #[derive(Component)]
 struct Shape;
 
@@ -44,60 +125,26 @@ fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut images: ResMut<Assets<Image>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
     mut line_materials: ResMut<Assets<LineMaterial>>,
+    args: Res<Args>,
 ) {
     let debug_material = line_materials.add(LineMaterial {
         color: LinearRgba::GREEN,
     });
 
-    let (mut points, header) = read_las("2743_1234.las");
-    let bounds = header.bounds();
-
-    let size = [
-        bounds.max.x - bounds.min.x,
-        bounds.max.y - bounds.min.y,
-        bounds.max.z - bounds.min.z,
-    ]
-    .into_iter()
-    .max_by(|a, b| a.partial_cmp(b).unwrap())
-    .unwrap() as f32;
-    println!("octree size: {}", size);
+    // Headers and points are read on the async compute pool. The scene is framed once the
+    // headers are in, previews of the tiles show up as they finish.
+    let progress = LoadProgress::default();
+    loading::spawn_progress_panel(&mut commands);
+    commands.insert_resource(Loading::start(&args, &progress));
+    commands.insert_resource(progress);
 
-    let mut tree = octree::Octree::new(size);
-    println!("TREE {:#?}", tree);
-    let lines = gen_debug_lines(&tree);
-
-    
-    transform_move(&mut points, &bounds);
-    println!("importing data to octree...");
-    tree.import(&points);
-
-    println!("exporting data from octree...");
-    let modified_points = tree.export();
-
-    drop(points);
-
-    let mesh: Mesh = Mesh::new(
-        bevy::render::mesh::PrimitiveTopology::PointList,
-        RenderAssetUsages::default(),
-    )
-    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, modified_points);
-
-    
-
-    let mesh = meshes.add(mesh);
-    let lines = meshes.add(lines);
-    //let sphere = meshes.add(sphere);
-
-    commands.spawn((
-        PbrBundle {
-            mesh: mesh,
-            transform: Transform::from_xyz(0.0, 0.0, 0.0),
-            ..default()
-        },
-        Shape,
-    ));
+    // Filled with the octree cells when the scene is framed.
+    let lines = meshes.add(
+        Mesh::new(bevy::render::mesh::PrimitiveTopology::LineList, RenderAssetUsages::default())
+            .with_inserted_indices(bevy::render::mesh::Indices::U32(Vec::new()))
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, Vec::<[f32; 3]>::new()),
+    );
 
     commands.spawn((
         MaterialMeshBundle {
@@ -109,81 +156,183 @@ fn setup(
         Shape,
     ));
 
-   
     commands.spawn((
         Camera3dBundle {
-            transform: Transform::from_xyz(-500.0, 500., -500.0)
-                .looking_at(Vec3::new(500., 500., 0.), Vec3::Y),
+            camera_3d: Camera3d {
+                // Bound by the eye-dome lighting pass.
+                depth_texture_usages: (TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING).into(),
+                ..default()
+            },
             ..default()
         },
         PanOrbitCamera::default(),
+        args.edl,
     ));
-
 }
 
-fn read_las(path: &str) -> (Vec<[f32; 3]>, las::header::Header) {
-    let mut reader = las::Reader::from_path(path).expect("las file not found, provide 2743_1234.las in project directory");
-    let header = las::Read::header(&reader).clone();
-    println!("{header:#?}");
+// Reads the headers of every input, so the scene frame is known before any points are, and
+// builds the empty octree around it. Returns the tree, the scene bounds, the number of points and
+// the octree size.
+fn build_scene(args: &Args) -> Result<(octree::Octree, las::Bounds, u64, f32), String> {
+    let mut bounds: Option<las::Bounds> = None;
+    let mut point_count = 0;
+    for path in args.inputs.iter() {
+        let header = read_header(path).map_err(|error| format!("failed to read {}: {error}", path.display()))?;
+        point_count += header.number_of_points();
+        let tile_bounds = header.bounds();
+        bounds = Some(match bounds {
+            Some(bounds) => merge_bounds(&bounds, &tile_bounds),
+            None => tile_bounds,
+        });
+    }
+    let bounds = bounds.ok_or("no inputs to read")?;
+    println!("scene of {} tiles, bounds: {:?}", args.inputs.len(), bounds);
 
-    let cache_path = std::path::Path::new(CACHE_PATH);
-    let points = if cache_path.exists() {
-        println!("reading cached points...");
-        let points = std::fs::read(CACHE_PATH).expect("faild to read cache file, delete it");
-        println!("deserializing cached points...");
-        let points = bincode::deserialize::<Vec<[f32; 3]>>(&points)
-            .expect("failed to deserialize cache file, delete it");
-        println!("reading/deserializing completed");
-        points
-    } else {
-        let mut points = Vec::with_capacity((header.number_of_points() / 3) as usize);
+    // A single point, or points that all share a position, still get a tree to live in.
+    let extent = [
+        bounds.max.x - bounds.min.x,
+        bounds.max.y - bounds.min.y,
+        bounds.max.z - bounds.min.z,
+    ]
+    .into_iter()
+    .fold(0.0, f64::max) as f32;
+    let size = extent.max(MIN_SCENE_SIZE);
+    println!("octree size: {}", size);
 
-        let mut p_i: usize = 0;
+    let tree = args
+        .octree_builder(size)
+        .origin(tree_origin(&bounds, size, args.transform))
+        .build()
+        .map_err(|error| format!("failed to create the octree: {error}"))?;
+    Ok((tree, bounds, point_count, size))
+}
 
-        for wrapped_point in las::Read::points(&mut reader) {
-            let point = wrapped_point.unwrap();
+fn read_header(path: &Path) -> las::Result<las::header::Header> {
+    let reader = las::Reader::from_path(path)?;
+    Ok(las::Read::header(&reader).clone())
+}
 
-            points.extend([[point.x as f32, point.z as f32, point.y as f32]]);
+// Points are returned relative to the minimum of the header bounds, in viewer coordinates.
+// `progress` counts the points read so far, `verbose` prints the header.
+fn read_las(
+    path: &Path,
+    cache_dir: &Path,
+    progress: &AtomicU64,
+    verbose: bool,
+) -> Result<(TilePoints, las::header::Header), Box<dyn std::error::Error>> {
+    let mut reader = las::Reader::from_path(path)?;
+    let header = las::Read::header(&reader).clone();
+    if verbose {
+        println!("{header:#?}");
+    }
+    let laz_info = laz::LazInfo::from_header(&header);
+    if let (true, Some(laz_info)) = (verbose, laz_info.as_ref()) {
+        println!("{laz_info}");
+    }
 
-            if p_i % 100000 == 0 {
-                println!("reading points: {}", p_i);
+    let (points, attributes) = match cache::load(cache_dir, path, &header) {
+        Some(cached) => {
+            progress.fetch_add(cached.0.len() as u64, Ordering::Relaxed);
+            cached
+        }
+        None => {
+            let point_count = header.number_of_points();
+            println!("reading {} points...", point_count);
+            let (points, attributes) = match laz_info.as_ref() {
+                Some(laz_info) if laz_info.has_fixed_chunks() => {
+                    laz::read_points_parallel(path, laz_info, point_count, &header.bounds(), progress)?
+                }
+                Some(_) | None => {
+                    read_points(&mut reader, point_count, &header.bounds(), progress)?
+                }
+            };
+            println!("finished reading points: {}", points.len());
+
+            if let Err(error) = cache::store(cache_dir, path, &header, &points, &attributes) {
+                eprintln!("warning: failed to cache points of {}: {error}", path.display());
             }
-
-            p_i += 1;
+            (points, attributes)
         }
-        println!("finished reading points: {}", p_i);
-
-        let points_bytes = bincode::serialize(&points).unwrap();
-        std::fs::write(CACHE_PATH, points_bytes).unwrap();
-        points
     };
 
-    let result = (points, header);
-
-    result
+    Ok(((points, attributes), header))
 }
 
-
-fn transform_center(points: &mut [[f32; 3]], bounds: &las::Bounds) {
-    println!("transforming points...");
-    for [x, y, z] in points.iter_mut() {
-        *x = (*x - bounds.min.x as f32) - ((bounds.max.x as f32 - bounds.min.x as f32) / 2.0);
-        *y = (*y - bounds.min.y as f32) - ((bounds.max.y as f32 - bounds.min.y as f32) / 2.0);
-        *z -= bounds.min.z as f32;
+// Reads up to `count` points from the current position of `reader`, in viewer coordinates
+// relative to the minimum of `bounds`, which keeps them precise as f32.
+fn read_points(reader: &mut las::Reader, count: u64, bounds: &las::Bounds, progress: &AtomicU64) -> las::Result<TilePoints> {
+    let min = bounds.min;
+    let mut points = Vec::with_capacity(count as usize);
+    let mut attributes = Vec::with_capacity(count as usize);
+    for wrapped_point in las::Read::points(reader).take(count as usize) {
+        let point = wrapped_point?;
+        if points.len() % PROGRESS_STEP == PROGRESS_STEP - 1 {
+            progress.fetch_add(PROGRESS_STEP as u64, Ordering::Relaxed);
+        }
+        points.push([(point.x - min.x) as f32, (point.z - min.z) as f32, (point.y - min.y) as f32]);
+        attributes.push(PointAttributes {
+            intensity: point.intensity,
+            return_number: point.return_number,
+            classification: u8::from(point.classification),
+            point_source_id: point.point_source_id,
+            color: point.color.map(|color| [color.red, color.green, color.blue]),
+        });
     }
-    println!("finished transforming points.");
+    progress.fetch_add((points.len() % PROGRESS_STEP) as u64, Ordering::Relaxed);
+    Ok((points, attributes))
 }
 
-fn transform_move(points: &mut [[f32; 3]], bounds: &las::Bounds) {
-    println!("transforming points...");
+fn merge_bounds(a: &las::Bounds, b: &las::Bounds) -> las::Bounds {
+    let mut bounds = *a;
+    bounds.min.x = a.min.x.min(b.min.x);
+    bounds.min.y = a.min.y.min(b.min.y);
+    bounds.min.z = a.min.z.min(b.min.z);
+    bounds.max.x = a.max.x.max(b.max.x);
+    bounds.max.y = a.max.y.max(b.max.y);
+    bounds.max.z = a.max.z.max(b.max.z);
+    bounds
+}
 
-    for [x, y, z] in points.iter_mut() {
-        *x -= bounds.min.x as f32;
-        *y -= bounds.min.z as f32;
-        *z -= bounds.min.y as f32;
+// Minimum corner of the octree in viewer coordinates, where y is up, after the transform.
+fn tree_origin(bounds: &las::Bounds, size: f32, transform: TransformMode) -> [f32; 3] {
+    match transform {
+        TransformMode::Move => {
+            [0.0, 0.0, 0.0]
+        }
+        TransformMode::Center => {
+            [-size / 2.0, 0.0, -size / 2.0]
+        }
+        TransformMode::None => {
+            [bounds.min.x as f32, bounds.min.z as f32, bounds.min.y as f32]
+        }
     }
+}
 
-    println!("finished transforming points.");
+// Moves points read relative to the minimum of their tile into the scene frame picked by
+// `transform`. The offset is computed in f64, so tiles far from the origin stay precise.
+fn transform(points: &mut [[f32; 3]], tile: &las::Bounds, scene: &las::Bounds, transform: TransformMode) {
+    let origin = match transform {
+        TransformMode::Move => {
+            [scene.min.x, scene.min.y, scene.min.z]
+        }
+        TransformMode::Center => {
+            [(scene.min.x + scene.max.x) / 2.0, (scene.min.y + scene.max.y) / 2.0, scene.min.z]
+        }
+        TransformMode::None => {
+            [0.0, 0.0, 0.0]
+        }
+    };
+    let offset = [
+        (tile.min.x - origin[0]) as f32,
+        (tile.min.z - origin[2]) as f32,
+        (tile.min.y - origin[1]) as f32,
+    ];
+
+    for point in points.iter_mut() {
+        for (value, offset) in point.iter_mut().zip(offset) {
+            *value += offset;
+        }
+    }
 }
 
 fn gen_debug_lines(tree: &octree::Octree) -> Mesh {
@@ -320,6 +469,25 @@ fn gen_debug_lines(tree: &octree::Octree) -> Mesh {
     lines
 }
 
+// Writes OBJ or PLY, picked by the extension of `path`.
+fn write_surface(surface: &octree::reconstruction::TriangleMesh, path: &Path) -> std::io::Result<()> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    match SurfaceFormat::from_path(path) {
+        Some(SurfaceFormat::Ply) => surface.write_ply(&mut writer)?,
+        Some(SurfaceFormat::Obj) | None => surface.write_obj(&mut writer)?,
+    }
+    std::io::Write::flush(&mut writer)
+}
+
+fn gen_surface_mesh(surface: &octree::reconstruction::TriangleMesh) -> Mesh {
+    Mesh::new(
+        bevy::render::mesh::PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_indices(bevy::render::mesh::Indices::U32(surface.indices.clone()))
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, surface.positions.clone())
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, surface.normals.clone())
+}
 
 impl Material for LineMaterial {
     fn fragment_shader() -> ShaderRef {
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use bevy::{
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    prelude::*,
+    render::{
+        mesh::{Indices, MeshVertexBufferLayoutRef, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        render_resource::{
+            AsBindGroup, Buffer, BufferInitDescriptor, BufferUsages, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError,
+        },
+        renderer::{RenderDevice, RenderQueue},
+    },
+};
+
+// Every point is drawn as a quad facing the camera, two triangles the shader builds from the
+// vertex index.
+const VERTICES_PER_POINT: usize = 6;
+// Smallest index mesh, smaller nodes share it.
+const MIN_QUAD_CAPACITY: usize = 1024;
+// A `SplatPoint` of the shader, a vec3 and a u32 fill 16 bytes.
+const SPLAT_POINT_SIZE: usize = 16;
+
+const SIZE_STEP: f32 = 1.25;
+const MIN_SIZE: f32 = 1.0;
+const MAX_SIZE: f32 = 64.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SizeMode {
+    // The same size in pixels at every distance.
+    #[default]
+    Fixed,
+    // A multiple of the point spacing in world units, so near points get bigger.
+    Attenuated,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Shape {
+    #[default]
+    Round,
+    Square,
+}
+
+// The current settings, which new materials start with.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct SplatSettings {
+    // Pixels with `SizeMode::Fixed`, multiples of the point spacing with `SizeMode::Attenuated`.
+    pub size: f32,
+    pub size_mode: SizeMode,
+    pub shape: Shape,
+}
+
+// What the shader reads of a point, every point is stored once.
+#[derive(Debug, Clone, Copy)]
+pub struct SplatPoint {
+    pub position: [f32; 3],
+    // `pack4x8unorm` colour, points with zero alpha are hidden.
+    pub color: u32,
+}
+
+// Index meshes the splats are drawn with, shared by the materials of up to their capacity of
+// points. Indices count up from zero, so the shader finds the point of a vertex at `index / 6`.
+#[derive(Resource, Default)]
+pub struct SplatQuads(HashMap<usize, Handle<Mesh>>);
+
+// Every material holds the points of one mesh.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+pub struct SplatMaterial {
+    // Multiplies the colours of the points, which are white with the uniform colour mode.
+    #[uniform(0)]
+    pub color: LinearRgba,
+    #[uniform(0)]
+    pub size: f32,
+    // Average distance between neighbouring points in world units.
+    #[uniform(0)]
+    pub spacing: f32,
+    // Pixel limits of attenuated splats.
+    #[uniform(0)]
+    pub min_size: f32,
+    #[uniform(0)]
+    pub max_size: f32,
+    #[uniform(0)]
+    pub size_mode: u32,
+    #[uniform(0)]
+    pub shape: u32,
+    // `SplatPoint`s made by `gen_splat_buffer`.
+    #[storage(1, read_only, buffer, visibility(vertex))]
+    pub points: Buffer,
+}
+
+impl Default for SplatSettings {
+    fn default() -> Self {
+        Self {
+            size: 2.0,
+            size_mode: SizeMode::Fixed,
+            shape: Shape::Round,
+        }
+    }
+}
+
+impl SizeMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "fixed" => Some(SizeMode::Fixed),
+            "attenuated" => Some(SizeMode::Attenuated),
+            _ => None,
+        }
+    }
+}
+
+impl Shape {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "round" => Some(Shape::Round),
+            "square" => Some(Shape::Square),
+            _ => None,
+        }
+    }
+}
+
+impl SplatMaterial {
+    pub fn new(settings: &SplatSettings, spacing: f32, points: Buffer) -> Self {
+        let mut material = Self {
+            color: LinearRgba::WHITE,
+            size: 0.0,
+            spacing,
+            min_size: MIN_SIZE,
+            max_size: MAX_SIZE,
+            size_mode: 0,
+            shape: 0,
+            points,
+        };
+        material.set_settings(settings);
+        material
+    }
+
+    pub fn settings(&self) -> SplatSettings {
+        SplatSettings {
+            size: self.size,
+            size_mode: if self.size_mode == 0 { SizeMode::Fixed } else { SizeMode::Attenuated },
+            shape: if self.shape == 0 { Shape::Round } else { Shape::Square },
+        }
+    }
+
+    // The discriminants match the constants of the shader.
+    pub fn set_settings(&mut self, settings: &SplatSettings) {
+        self.size = settings.size;
+        self.size_mode = match settings.size_mode {
+            SizeMode::Fixed => 0,
+            SizeMode::Attenuated => 1,
+        };
+        self.shape = match settings.shape {
+            Shape::Round => 0,
+            Shape::Square => 1,
+        };
+    }
+}
+
+impl Material for SplatMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/splat_material.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/splat_material.wgsl".into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Quads always face the camera, their winding depends on the corner order only.
+        descriptor.primitive.cull_mode = None;
+        // Vertices are pulled from the points, the mesh only provides the indices.
+        descriptor.vertex.buffers.clear();
+        Ok(())
+    }
+}
+
+impl SplatQuads {
+    // Mesh drawing `point_count` or a few more splats, the shader drops the vertices past the
+    // points of the material.
+    pub fn mesh(&mut self, point_count: usize, meshes: &mut Assets<Mesh>) -> Handle<Mesh> {
+        let capacity = point_count.next_power_of_two().max(MIN_QUAD_CAPACITY);
+        self.0.entry(capacity).or_insert_with(|| meshes.add(gen_quad_mesh(capacity))).clone()
+    }
+}
+
+// Indices of `capacity` splats. The single position is never read, it keeps the vertex buffer
+// from being empty.
+fn gen_quad_mesh(capacity: usize) -> Mesh {
+    Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::RENDER_WORLD)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vec![[0.0f32; 3]])
+        .with_inserted_indices(Indices::U32((0..(capacity * VERTICES_PER_POINT) as u32).collect()))
+}
+
+// White splats at `points`, see `PointColors::apply` and `ClassVisibility::apply`.
+pub fn gen_splat_points(points: &[[f32; 3]]) -> Vec<SplatPoint> {
+    let white = pack_color([1.0; 4]);
+    points.iter().map(|point| SplatPoint { position: *point, color: white }).collect()
+}
+
+// Storage buffers cannot be empty, `points` must not be.
+pub fn gen_splat_buffer(device: &RenderDevice, points: &[SplatPoint]) -> Buffer {
+    device.create_buffer_with_data(&BufferInitDescriptor {
+        label: Some("splat points"),
+        contents: &splat_bytes(points),
+        usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+    })
+}
+
+// Replaces the points of a buffer made by `gen_splat_buffer` with as many others.
+pub fn write_splat_buffer(queue: &RenderQueue, buffer: &Buffer, points: &[SplatPoint]) {
+    queue.write_buffer(buffer, 0, &splat_bytes(points));
+}
+
+// Like `pack4x8unorm` of the shader, the first component in the lowest byte.
+pub fn pack_color(color: [f32; 4]) -> u32 {
+    color
+        .iter()
+        .enumerate()
+        .fold(0, |packed, (i, value)| packed | ((value.clamp(0.0, 1.0) * 255.0).round() as u32) << (8 * i))
+}
+
+fn splat_bytes(points: &[SplatPoint]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(points.len() * SPLAT_POINT_SIZE);
+    for point in points {
+        for value in point.position {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&point.color.to_le_bytes());
+    }
+    bytes
+}
+
+// Keys: +/- change the size, S switches round and square, M switches fixed and attenuated sizes.
+pub fn adjust_splats(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut current: ResMut<SplatSettings>,
+    mut materials: ResMut<Assets<SplatMaterial>>,
+) {
+    let mut change = |change: &dyn Fn(&mut SplatSettings)| {
+        change(&mut current);
+        for (_, material) in materials.iter_mut() {
+            let mut settings = material.settings();
+            change(&mut settings);
+            material.set_settings(&settings);
+        }
+    };
+
+    if keys.just_pressed(KeyCode::Equal) || keys.just_pressed(KeyCode::NumpadAdd) {
+        change(&|settings| settings.size = (settings.size * SIZE_STEP).min(MAX_SIZE));
+    }
+    if keys.just_pressed(KeyCode::Minus) || keys.just_pressed(KeyCode::NumpadSubtract) {
+        change(&|settings| settings.size = (settings.size / SIZE_STEP).max(0.01));
+    }
+    if keys.just_pressed(KeyCode::KeyS) {
+        change(&|settings| {
+            settings.shape = match settings.shape {
+                Shape::Round => Shape::Square,
+                Shape::Square => Shape::Round,
+            }
+        });
+    }
+    if keys.just_pressed(KeyCode::KeyM) {
+        change(&|settings| {
+            settings.size_mode = match settings.size_mode {
+                SizeMode::Fixed => SizeMode::Attenuated,
+                SizeMode::Attenuated => SizeMode::Fixed,
+            }
+        });
+    }
+}
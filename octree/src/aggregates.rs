@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+
+use crate::{Octree, OctreeNode, PointAttributes};
+
+// Summary of the points stored below a node. Points shared by overlapping loose spheres are
+// counted once for every sphere holding them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Aggregates {
+    pub count: usize,
+    // Bounds of the points themselves, usually tighter than the cell.
+    pub bounds: Option<[[f32; 3]; 2]>,
+    pub sum: [f64; 3],
+    // Points per classification, only filled for points imported with attributes.
+    pub classifications: BTreeMap<u8, usize>,
+}
+
+impl Aggregates {
+    pub fn add(&mut self, point: &[f32; 3], attributes: Option<&PointAttributes>) {
+        self.count += 1;
+        self.bounds = Some(match self.bounds {
+            Some([min, max]) => [
+                std::array::from_fn(|axis| min[axis].min(point[axis])),
+                std::array::from_fn(|axis| max[axis].max(point[axis])),
+            ],
+            None => [*point, *point],
+        });
+        for (sum, value) in self.sum.iter_mut().zip(point) {
+            *sum += *value as f64;
+        }
+        if let Some(attributes) = attributes {
+            *self.classifications.entry(attributes.classification).or_insert(0) += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Aggregates) {
+        self.count += other.count;
+        self.bounds = match (self.bounds, other.bounds) {
+            (Some([min, max]), Some([other_min, other_max])) => Some([
+                std::array::from_fn(|axis| min[axis].min(other_min[axis])),
+                std::array::from_fn(|axis| max[axis].max(other_max[axis])),
+            ]),
+            (bounds, None) | (None, bounds) => bounds,
+        };
+        for (sum, other_sum) in self.sum.iter_mut().zip(other.sum) {
+            *sum += other_sum;
+        }
+        for (classification, count) in other.classifications.iter() {
+            *self.classifications.entry(*classification).or_insert(0) += count;
+        }
+    }
+
+    pub fn min(&self, axis: usize) -> Option<f32> {
+        self.bounds.map(|[min, _]| min[axis])
+    }
+
+    pub fn max(&self, axis: usize) -> Option<f32> {
+        self.bounds.map(|[_, max]| max[axis])
+    }
+
+    pub fn mean(&self) -> Option<[f32; 3]> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(self.sum.map(|sum| (sum / self.count as f64) as f32))
+    }
+
+    pub fn classification_count(&self, classification: u8) -> usize {
+        self.classifications.get(&classification).copied().unwrap_or(0)
+    }
+}
+
+impl Octree {
+    // Aggregates of the points inside `bounds`. Nodes whose points all lie inside are taken as
+    // a whole and nodes whose points all lie outside are skipped, only the rest is visited.
+    pub fn summarize(&self, bounds: &[[f32; 3]; 2]) -> Aggregates {
+        let mut output = Aggregates::default();
+        self.root.summarize(bounds, &mut output);
+        output
+    }
+
+    // Recomputes the aggregates of every node, needed after editing nodes by hand.
+    pub fn update_aggregates(&mut self) {
+        self.root.update_aggregates();
+    }
+}
+
+impl OctreeNode {
+    pub fn summarize(&self, bounds: &[[f32; 3]; 2], output: &mut Aggregates) {
+        let Some(node_bounds) = self.aggregates().bounds else {
+            return;
+        };
+        if !overlaps(&node_bounds, bounds) {
+            return;
+        }
+        if contains(bounds, &node_bounds) {
+            output.merge(self.aggregates());
+            return;
+        }
+
+        let attributes = self.attributes();
+        for (i, data_point) in self.data_points().iter().enumerate() {
+            if contains(bounds, &[*data_point, *data_point]) {
+                output.add(data_point, attributes.get(i));
+            }
+        }
+
+        if let Some(nodes) = self.nodes_ref() {
+            for node in nodes.iter() {
+                node.summarize(bounds, output);
+            }
+        }
+    }
+
+    pub fn update_aggregates(&mut self) {
+        if let Some(nodes) = self.nodes_mut() {
+            for node in nodes.iter_mut() {
+                node.update_aggregates();
+            }
+        }
+        self.refresh_aggregates();
+    }
+
+    // Recomputes the aggregates of this node from its own points and the aggregates of its
+    // children, which are assumed to be up to date.
+    pub fn refresh_aggregates(&mut self) {
+        let mut aggregates = Aggregates::default();
+        let attributes = self.attributes();
+        for (i, data_point) in self.data_points().iter().enumerate() {
+            aggregates.add(data_point, attributes.get(i));
+        }
+        if let Some(nodes) = self.nodes_ref() {
+            for node in nodes.iter() {
+                aggregates.merge(node.aggregates());
+            }
+        }
+        *self.aggregates_mut() = aggregates;
+    }
+}
+
+fn overlaps(a: &[[f32; 3]; 2], b: &[[f32; 3]; 2]) -> bool {
+    (0..3).all(|axis| a[0][axis] <= b[1][axis] && b[0][axis] <= a[1][axis])
+}
+
+// Whether `inner` lies within `outer`, boundaries included.
+fn contains(outer: &[[f32; 3]; 2], inner: &[[f32; 3]; 2]) -> bool {
+    (0..3).all(|axis| outer[0][axis] <= inner[0][axis] && inner[1][axis] <= outer[1][axis])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Aggregates;
+    use crate::{DuplicateWinner, LooseInsertion, Octree, OctreeNode, PointAttributes, SphereMode};
+
+    // Aggregates of every point stored below `node`, worked out from the points alone. Checks
+    // every node on the way.
+    fn brute_force(node: &OctreeNode) -> Aggregates {
+        let mut points = Vec::new();
+        collect(node, &mut points);
+
+        let mut expected = Aggregates::default();
+        for (point, attributes) in points.iter() {
+            expected.add(point, attributes.as_ref());
+        }
+        let found = node.aggregates();
+        assert_eq!(found.count, expected.count);
+        assert_eq!(found.bounds, expected.bounds);
+        assert_eq!(found.classifications, expected.classifications);
+        for axis in 0..3 {
+            assert!((found.sum[axis] - expected.sum[axis]).abs() < 1e-6, "{found:?} {expected:?}");
+        }
+
+        if let Some(nodes) = node.nodes_ref() {
+            for child in nodes.iter() {
+                brute_force(child);
+            }
+        }
+        expected
+    }
+
+    fn collect(node: &OctreeNode, points: &mut Vec<([f32; 3], Option<PointAttributes>)>) {
+        let attributes = node.attributes();
+        for (i, point) in node.data_points().iter().enumerate() {
+            points.push((*point, attributes.get(i).copied()));
+        }
+        if let Some(nodes) = node.nodes_ref() {
+            for child in nodes.iter() {
+                collect(child, points);
+            }
+        }
+    }
+
+    fn lattice(offset: f32, step: f32) -> (Vec<[f32; 3]>, Vec<PointAttributes>) {
+        let mut points = Vec::new();
+        let mut attributes = Vec::new();
+        for i in 0..6 {
+            for j in 0..6 {
+                for k in 0..6 {
+                    points.push([offset + i as f32 * step, offset + j as f32 * step, offset + k as f32 * step]);
+                    attributes.push(PointAttributes { classification: ((i + j + k) % 4) as u8, ..PointAttributes::default() });
+                }
+            }
+        }
+        (points, attributes)
+    }
+
+    #[test]
+    fn match_the_points_after_import_merge_and_dedup() {
+        let mut tree = Octree::builder(10.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        let (points, attributes) = lattice(0.5, 1.5);
+        tree.import_with_attributes(&points, &attributes).unwrap();
+        let root = brute_force(&tree.root);
+        assert_eq!(root.count, points.len());
+        assert_eq!(root.classification_count(0), tree.export_attributes().iter().filter(|attributes| attributes.classification == 0).count());
+
+        let mut other = Octree::builder(10.0)
+            .origin([10.0, 10.0, 10.0])
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        let (other_points, other_attributes) = lattice(10.5, 1.4);
+        other.import_with_attributes(&other_points, &other_attributes).unwrap();
+        tree.merge(other).unwrap();
+        assert_eq!(brute_force(&tree.root).count, points.len() + other_points.len());
+
+        // Copies of the first lattice, slightly moved.
+        let (copies, copy_attributes) = lattice(0.5001, 1.5);
+        tree.import_with_attributes(&copies, &copy_attributes).unwrap();
+        assert_eq!(tree.dedup(0.01, DuplicateWinner::First), copies.len());
+        let root = brute_force(&tree.root);
+        assert_eq!(root.count, points.len() + other_points.len());
+    }
+
+    #[test]
+    fn node_import_refreshes_inner_nodes() {
+        let mut tree = Octree::builder(10.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        tree.root.import(&[[1.0, 1.0, 1.0], [9.0, 2.0, 4.0]]);
+        let root = brute_force(&tree.root);
+        assert_eq!(root.count, tree.point_count());
+        assert!(root.count > 0);
+    }
+}
@@ -0,0 +1,10 @@
+// Per point values carried along with the position, named after the LAS point record fields.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PointAttributes {
+    pub intensity: u16,
+    pub return_number: u8,
+    pub classification: u8,
+    pub point_source_id: u16,
+    pub color: Option<[u16; 3]>,
+}
@@ -0,0 +1,234 @@
+use crate::{
+    gen_cube, octree_divide_into_cube, NodeKey, Octree, OctreeCube, OctreeError, OctreeNode,
+    OctreeSphere,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    Cube,
+    Sphere,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Division {
+    // Every level down to `Layout::depth` is created up front.
+    Eager,
+    // Levels below `Layout::depth` are created once a leaf holds more than `capacity` points,
+    // down to `max_depth`.
+    Lazy { capacity: usize, max_depth: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SphereMode {
+    // Spheres touch the faces of their cell, points in the cell corners are not stored. The
+    // default like before the loose mode existed, trees that must keep every point use `Loose`.
+    #[default]
+    Inscribed,
+    // Spheres pass through the corners of their cell and overlap their neighbours, so every
+    // point of the cell is stored.
+    Loose(LooseInsertion),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LooseInsertion {
+    // A point is stored in every sphere containing it.
+    All,
+    // A point is only stored in the sphere of the cell containing it, whose middle is closest.
+    BestFit,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Layout {
+    // Node kind per depth, the last entry repeats for deeper levels.
+    pub kinds: Vec<NodeKind>,
+    pub depth: usize,
+    pub division: Division,
+    pub sphere_mode: SphereMode,
+}
+
+#[derive(Debug, Clone)]
+pub struct OctreeBuilder {
+    size: f32,
+    origin: [f32; 3],
+    layout: Layout,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            kinds: vec![NodeKind::Cube, NodeKind::Cube, NodeKind::Sphere, NodeKind::Sphere],
+            depth: 3,
+            division: Division::Eager,
+            sphere_mode: SphereMode::default(),
+        }
+    }
+}
+
+impl SphereMode {
+    // How far, relative to its size, a point stored below a node can lie outside of its cell.
+    pub fn margin(&self) -> f32 {
+        match self {
+            SphereMode::Loose(LooseInsertion::All) => (3f32.sqrt() - 1.0) / 2.0,
+            SphereMode::Inscribed | SphereMode::Loose(LooseInsertion::BestFit) => 0.0,
+        }
+    }
+}
+
+impl NodeKind {
+    pub fn node(&self, cube_points: [[f32; 3]; 8], sphere_mode: SphereMode) -> OctreeNode {
+        match (self, sphere_mode) {
+            (NodeKind::Cube, _) => {
+                OctreeNode::Cube(OctreeCube { cube_points, nodes: None, aggregates: Box::default() })
+            }
+            (NodeKind::Sphere, SphereMode::Inscribed) => {
+                OctreeNode::Sphere(Box::new(OctreeSphere::new(cube_points)))
+            }
+            (NodeKind::Sphere, SphereMode::Loose(_)) => {
+                OctreeNode::Sphere(Box::new(OctreeSphere::loose(cube_points)))
+            }
+        }
+    }
+}
+
+impl Layout {
+    pub fn kind(&self, depth: usize) -> NodeKind {
+        self.kinds
+            .get(depth)
+            .or(self.kinds.last())
+            .copied()
+            .unwrap_or(NodeKind::Sphere)
+    }
+
+    pub fn max_depth(&self) -> usize {
+        match self.division {
+            Division::Eager => self.depth,
+            Division::Lazy { max_depth, .. } => max_depth,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), OctreeError> {
+        if self.kinds.is_empty() {
+            return Err(OctreeError::EmptyLayout);
+        }
+        if self.max_depth() > NodeKey::MAX_DEPTH as usize {
+            return Err(OctreeError::DepthTooLarge(self.max_depth()));
+        }
+
+        let leaf_depths = match self.division {
+            Division::Eager => self.depth..=self.depth,
+            Division::Lazy { capacity, max_depth } => {
+                if capacity == 0 {
+                    return Err(OctreeError::ZeroCapacity);
+                }
+                if max_depth < self.depth {
+                    return Err(OctreeError::DepthTooLarge(self.depth));
+                }
+                self.depth..=max_depth
+            }
+        };
+        for depth in leaf_depths {
+            if self.kind(depth) == NodeKind::Cube {
+                return Err(OctreeError::LeafCannotHoldPoints { depth });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Builds the node at `depth` and every level below it down to `self.depth`.
+    pub fn gen_node(&self, cube_points: [[f32; 3]; 8], depth: usize) -> OctreeNode {
+        let mut node = self.kind(depth).node(cube_points, self.sphere_mode);
+        if depth < self.depth {
+            #[cfg(feature = "rayon")]
+            let nodes = {
+                use rayon::prelude::*;
+
+                let nodes: Vec<OctreeNode> = octree_divide_into_cube(&cube_points)
+                    .par_iter()
+                    .map(|cube| self.gen_node(*cube, depth + 1))
+                    .collect();
+                nodes.try_into().unwrap()
+            };
+            #[cfg(not(feature = "rayon"))]
+            let nodes = octree_divide_into_cube(&cube_points).map(|cube| self.gen_node(cube, depth + 1));
+            node.set_nodes(nodes);
+        }
+        node
+    }
+
+    // Adds a level on top, used when the root is replaced by a bigger one. Fails, leaving the
+    // layout as it is, when the deepest level would pass `NodeKey::MAX_DEPTH`.
+    pub(crate) fn grow(&mut self) -> Result<(), OctreeError> {
+        let mut grown = self.clone();
+        grown.kinds.insert(0, self.kind(0));
+        grown.depth += 1;
+        if let Division::Lazy { max_depth, .. } = &mut grown.division {
+            *max_depth += 1;
+        }
+        grown.validate()?;
+        *self = grown;
+        Ok(())
+    }
+}
+
+impl OctreeBuilder {
+    pub fn new(size: f32) -> Self {
+        Self {
+            size,
+            origin: [0.0, 0.0, 0.0],
+            layout: Layout::default(),
+        }
+    }
+
+    pub fn origin(mut self, origin: [f32; 3]) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn kind(mut self, depth: usize, kind: NodeKind) -> Self {
+        if self.layout.kinds.len() <= depth {
+            let last = self.layout.kind(depth);
+            self.layout.kinds.resize(depth + 1, last);
+        }
+        self.layout.kinds[depth] = kind;
+        self
+    }
+
+    pub fn kinds(mut self, kinds: &[NodeKind]) -> Self {
+        self.layout.kinds = kinds.to_vec();
+        self
+    }
+
+    pub fn depth(mut self, depth: usize) -> Self {
+        self.layout.depth = depth;
+        self
+    }
+
+    pub fn division(mut self, division: Division) -> Self {
+        self.layout.division = division;
+        self
+    }
+
+    pub fn sphere_mode(mut self, sphere_mode: SphereMode) -> Self {
+        self.layout.sphere_mode = sphere_mode;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), OctreeError> {
+        if !self.size.is_finite() || self.size <= 0.0 {
+            return Err(OctreeError::InvalidSize(self.size));
+        }
+        if !self.origin.iter().all(|value| value.is_finite()) {
+            return Err(OctreeError::InvalidOrigin(self.origin));
+        }
+        self.layout.validate()
+    }
+
+    pub fn build(self) -> Result<Octree, OctreeError> {
+        self.validate()?;
+
+        let [x, y, z] = self.origin;
+        let root = self.layout.gen_node(gen_cube(x, y, z, self.size), 0);
+        Ok(Octree { root, layout: self.layout })
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+
+use crate::{distance_squared, LooseInsertion, Octree, OctreeNode, PointAttributes, SphereMode};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateWinner {
+    // The point stored first, in `export` order.
+    #[default]
+    First,
+    Last,
+    HighestIntensity,
+    // The point with the lowest return number, ties go to the one stored first.
+    FirstReturn,
+}
+
+impl Octree {
+    // Merges points within `tolerance` of each other into the one picked by `winner`, which keeps
+    // its position and attributes. A tolerance of zero only merges identical positions. Returns
+    // how many points were removed.
+    pub fn dedup(&mut self, tolerance: f32, winner: DuplicateWinner) -> usize {
+        // Loose spheres also hold copies of points from neighbouring cells, only the copy stored
+        // in the cell containing the point takes part.
+        let copies = self.layout.sphere_mode == SphereMode::Loose(LooseInsertion::All);
+        let mut points: Vec<([f32; 3], Option<PointAttributes>)> = Vec::new();
+        self.for_each_node(|key, node| {
+            let attributes = node.attributes();
+            for (i, data_point) in node.data_points().iter().enumerate() {
+                if !copies || self.leaf_key(data_point) == key {
+                    points.push((*data_point, attributes.get(i).copied()));
+                }
+            }
+        });
+
+        let cell = |point: &[f32; 3]| -> [i64; 3] {
+            if tolerance > 0.0 {
+                point.map(|value| (value / tolerance).floor() as i64)
+            } else {
+                point.map(|value| (value + 0.0).to_bits() as i64)
+            }
+        };
+        let reach = if tolerance > 0.0 { 1 } else { 0 };
+
+        let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+        for (id, (data_point, _)) in points.iter().enumerate() {
+            grid.entry(cell(data_point)).or_default().push(id);
+        }
+
+        let tolerance_squared = tolerance * tolerance;
+        let mut removed = vec![false; points.len()];
+        let mut candidates = Vec::new();
+        for id in 0..points.len() {
+            if removed[id] {
+                continue;
+            }
+
+            candidates.clear();
+            let [x, y, z] = cell(&points[id].0);
+            for dx in -reach..=reach {
+                for dy in -reach..=reach {
+                    for dz in -reach..=reach {
+                        let Some(ids) = grid.get(&[x + dx, y + dy, z + dz]) else {
+                            continue;
+                        };
+                        candidates.extend(ids.iter().copied().filter(|other| {
+                            !removed[*other] && distance_squared(&points[*other].0, &points[id].0) <= tolerance_squared
+                        }));
+                    }
+                }
+            }
+            if candidates.len() < 2 {
+                continue;
+            }
+
+            candidates.sort_unstable();
+            let kept = winner.pick(&candidates, &points);
+            for other in candidates.iter() {
+                if *other != kept {
+                    removed[*other] = true;
+                }
+            }
+        }
+
+        let removed_count = removed.iter().filter(|removed| **removed).count();
+        if removed_count == 0 {
+            return 0;
+        }
+
+        let has_attributes = points.iter().any(|(_, attributes)| attributes.is_some());
+        let (data_points, attributes): (Vec<[f32; 3]>, Vec<PointAttributes>) = points
+            .into_iter()
+            .zip(removed)
+            .filter(|(_, removed)| !removed)
+            .map(|((data_point, attributes), _)| (data_point, attributes.unwrap_or_default()))
+            .unzip();
+
+        clear(&mut self.root);
+        let attributes = if has_attributes { attributes.as_slice() } else { &[] };
+        self.root.import_routed(&data_points, attributes, 0, &self.layout);
+        self.update_aggregates();
+
+        removed_count
+    }
+}
+
+impl DuplicateWinner {
+    // `candidates` are sorted, so earlier ids were stored first.
+    fn pick(&self, candidates: &[usize], points: &[([f32; 3], Option<PointAttributes>)]) -> usize {
+        let attributes = |id: &usize| points[*id].1.unwrap_or_default();
+        match self {
+            DuplicateWinner::First => {
+                candidates[0]
+            }
+            DuplicateWinner::Last => {
+                candidates[candidates.len() - 1]
+            }
+            DuplicateWinner::HighestIntensity => {
+                *candidates.iter().rev().max_by_key(|id| attributes(id).intensity).unwrap()
+            }
+            DuplicateWinner::FirstReturn => {
+                *candidates.iter().min_by_key(|id| attributes(id).return_number).unwrap()
+            }
+        }
+    }
+}
+
+fn clear(node: &mut OctreeNode) {
+    node.take_data_points();
+    if let Some(nodes) = node.nodes_mut() {
+        for node in nodes.iter_mut() {
+            clear(node);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{LooseInsertion, Octree, OctreeBuilder, PointAttributes, SphereMode};
+
+    use super::DuplicateWinner;
+
+    fn attributes(intensity: u16, return_number: u8) -> PointAttributes {
+        PointAttributes { intensity, return_number, ..PointAttributes::default() }
+    }
+
+    // Three points within a millimetre of each other and two far apart.
+    fn tree() -> Octree {
+        let mut tree = Octree::builder(10.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        let points = [[1.0, 1.0, 1.0], [1.0005, 1.0, 1.0], [1.0, 1.0005, 1.0], [5.0, 5.0, 5.0], [8.0, 2.0, 3.0]];
+        let attributes = [attributes(10, 2), attributes(30, 1), attributes(20, 3), attributes(5, 1), attributes(5, 1)];
+        tree.import_with_attributes(&points, &attributes).unwrap();
+        tree
+    }
+
+    #[test]
+    fn removes_points_within_the_tolerance() {
+        let winners = [
+            (DuplicateWinner::First, 10),
+            (DuplicateWinner::Last, 20),
+            (DuplicateWinner::HighestIntensity, 30),
+            (DuplicateWinner::FirstReturn, 30),
+        ];
+        for (winner, intensity) in winners {
+            let mut tree = tree();
+            assert_eq!(tree.dedup(0.001, winner), 2, "{winner:?}");
+            assert_eq!(tree.point_count(), 3);
+
+            let kept = tree.export_attributes();
+            assert!(kept.iter().any(|attributes| attributes.intensity == intensity), "{winner:?}");
+            assert_eq!(kept.iter().filter(|attributes| attributes.intensity >= 10).count(), 1);
+        }
+    }
+
+    #[test]
+    fn zero_tolerance_only_merges_identical_positions() {
+        let mut tree = tree();
+        tree.import(&[[5.0, 5.0, 5.0], [5.0, 5.0, 5.0]]);
+        assert_eq!(tree.dedup(0.0, DuplicateWinner::First), 2);
+        assert_eq!(tree.point_count(), 5);
+        assert_eq!(tree.dedup(0.0, DuplicateWinner::First), 0);
+    }
+
+    #[test]
+    fn loose_copies_are_not_duplicates() {
+        let mut tree = OctreeBuilder::new(10.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::All))
+            .build()
+            .unwrap();
+        // Next to the middle of the root, so neighbouring loose spheres hold copies.
+        let points = [[4.99, 5.01, 5.0], [5.02, 4.98, 5.01], [1.0, 1.0, 1.0], [10.0, 10.0, 10.0]];
+        tree.import(&points);
+        let stored = tree.point_count();
+        assert!(stored > points.len());
+
+        assert_eq!(tree.dedup(0.0, DuplicateWinner::First), 0);
+        assert_eq!(tree.point_count(), stored);
+        assert_eq!(tree.dedup(0.1, DuplicateWinner::First), 1);
+    }
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OctreeError {
+    InvalidSize(f32),
+    InvalidOrigin([f32; 3]),
+    EmptyLayout,
+    DepthTooLarge(usize),
+    ZeroCapacity,
+    NotDivided,
+    ChildIndexOutOfRange(usize),
+    NonFinitePoint { index: usize, point: [f32; 3] },
+    // Cubes do not store data points, so every level that can end up as a leaf must be a sphere.
+    LeafCannotHoldPoints { depth: usize },
+    AttributeCountMismatch { points: usize, attributes: usize },
+    NormalCountMismatch { points: usize, normals: usize },
+    // Reconstruction cells must be positive and large enough for grid indices to fit an `i32`.
+    InvalidCellSize(f32),
+}
+
+impl fmt::Display for OctreeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OctreeError::InvalidSize(size) => {
+                write!(f, "octree size must be finite and positive, got {size}")
+            }
+            OctreeError::InvalidOrigin(origin) => {
+                write!(f, "octree origin must be finite, got {origin:?}")
+            }
+            OctreeError::EmptyLayout => {
+                write!(f, "octree layout has no node kinds")
+            }
+            OctreeError::DepthTooLarge(depth) => {
+                write!(f, "octree depth {depth} is larger than the supported {}", crate::NodeKey::MAX_DEPTH)
+            }
+            OctreeError::ZeroCapacity => {
+                write!(f, "lazy division needs a leaf capacity of at least one point")
+            }
+            OctreeError::NotDivided => {
+                write!(f, "node has no children")
+            }
+            OctreeError::ChildIndexOutOfRange(i) => {
+                write!(f, "child index {i} is out of range, nodes have 8 children")
+            }
+            OctreeError::NonFinitePoint { index, point } => {
+                write!(f, "point {index} has a non-finite coordinate: {point:?}")
+            }
+            OctreeError::LeafCannotHoldPoints { depth } => {
+                write!(f, "leaves at depth {depth} are cubes, which cannot hold data points")
+            }
+            OctreeError::AttributeCountMismatch { points, attributes } => {
+                write!(f, "{points} points were given with {attributes} attributes, every point needs its own")
+            }
+            OctreeError::NormalCountMismatch { points, normals } => {
+                write!(f, "{points} points were given with {normals} normals, every point needs its own")
+            }
+            OctreeError::InvalidCellSize(cell_size) => {
+                write!(f, "cell size must be finite, positive and not too small for the extent of the points, got {cell_size}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OctreeError {}
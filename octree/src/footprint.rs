@@ -0,0 +1,544 @@
+use std::{collections::HashMap, fmt::Write};
+
+use crate::Octree;
+
+// LAS x/y when the points keep their file order.
+pub const XY: [usize; 2] = [0, 1];
+// LAS x/y for points imported the way las_viewer does, with the height on the second axis.
+pub const XZ: [usize; 2] = [0, 2];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Polygon {
+    // Counter-clockwise ring without a repeated closing point.
+    pub exterior: Vec<[f32; 2]>,
+    // Clockwise rings, same layout as `exterior`.
+    pub holes: Vec<Vec<[f32; 2]>>,
+}
+
+pub fn project(points: &[[f32; 3]], axes: [usize; 2]) -> Vec<[f32; 2]> {
+    points.iter().map(|point| [point[axes[0]], point[axes[1]]]).collect()
+}
+
+// Outline of `points` on the plane of `axes`, such as the result of a query. The convex hull when
+// `alpha` is `None`, the alpha shape otherwise, see `concave_hull`. Empty when the points do not
+// span an area.
+pub fn footprint(points: &[[f32; 3]], axes: [usize; 2], alpha: Option<f32>) -> Vec<Polygon> {
+    let mut projected = project(points, axes);
+    projected.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+    projected.dedup();
+
+    match alpha {
+        Some(alpha) => concave_hull(&projected, alpha),
+        None => convex_hull(&projected).into_iter().collect(),
+    }
+}
+
+impl Octree {
+    // Outline of every point in the tree, see `footprint`.
+    pub fn footprint(&self, axes: [usize; 2], alpha: Option<f32>) -> Vec<Polygon> {
+        footprint(&self.export(), axes, alpha)
+    }
+}
+
+// Andrew's monotone chain, `None` when the points do not span an area.
+pub fn convex_hull(points: &[[f32; 2]]) -> Option<Polygon> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return None;
+    }
+
+    let mut lower: Vec<[f32; 2]> = Vec::new();
+    for point in sorted.iter() {
+        while lower.len() >= 2 && orient(&lower[lower.len() - 2], &lower[lower.len() - 1], point) <= 0.0 {
+            lower.pop();
+        }
+        lower.push(*point);
+    }
+
+    let mut upper: Vec<[f32; 2]> = Vec::new();
+    for point in sorted.iter().rev() {
+        while upper.len() >= 2 && orient(&upper[upper.len() - 2], &upper[upper.len() - 1], point) <= 0.0 {
+            upper.pop();
+        }
+        upper.push(*point);
+    }
+
+    lower.pop();
+    upper.pop();
+    lower.extend(upper);
+    if lower.len() < 3 {
+        return None;
+    }
+
+    Some(Polygon { exterior: lower, holes: Vec::new() })
+}
+
+// Alpha shape of the points: the union of Delaunay triangles whose circumradius is at most
+// `alpha`. Disconnected parts come back as separate polygons, gaps wider than about twice
+// `alpha` become holes.
+pub fn concave_hull(points: &[[f32; 2]], alpha: f32) -> Vec<Polygon> {
+    let triangulation = Triangulation::new(points);
+    let alpha = alpha as f64;
+
+    let kept: Vec<bool> = triangulation
+        .triangles
+        .iter()
+        .map(|triangle| {
+            triangle.alive
+                && !triangulation.touches_super(triangle)
+                && triangulation.circumradius(triangle) <= alpha
+        })
+        .collect();
+
+    // Boundary edges keep the orientation of their counter-clockwise triangle.
+    let mut edges: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, triangle) in triangulation.triangles.iter().enumerate() {
+        if !kept[i] {
+            continue;
+        }
+        for edge in 0..3 {
+            let outside = triangle.neighbours[edge].is_none_or(|neighbour| !kept[neighbour]);
+            if outside {
+                let a = triangle.vertices[(edge + 1) % 3];
+                let b = triangle.vertices[(edge + 2) % 3];
+                edges.entry(a).or_default().push(b);
+            }
+        }
+    }
+
+    let mut rings: Vec<Vec<[f32; 2]>> = Vec::new();
+    let mut starts: Vec<usize> = edges.keys().copied().collect();
+    starts.sort_unstable();
+    for start in starts {
+        while let Some(mut next) = edges.get_mut(&start).and_then(|targets| targets.pop()) {
+            let mut ring = vec![start];
+            while next != start {
+                ring.push(next);
+                match edges.get_mut(&next).and_then(|targets| targets.pop()) {
+                    Some(target) => next = target,
+                    None => break,
+                }
+            }
+            if ring.len() >= 3 {
+                rings.push(ring.into_iter().map(|i| triangulation.original(i)).collect());
+            }
+        }
+    }
+
+    let (exteriors, holes): (Vec<_>, Vec<_>) = rings.into_iter().partition(|ring| signed_area(ring) > 0.0);
+    let mut polygons: Vec<Polygon> = exteriors
+        .into_iter()
+        .map(|exterior| Polygon { exterior, holes: Vec::new() })
+        .collect();
+
+    for hole in holes {
+        let owner = polygons
+            .iter_mut()
+            .filter(|polygon| contains(&polygon.exterior, &hole[0]) || polygon.exterior.contains(&hole[0]))
+            .min_by(|a, b| signed_area(&a.exterior).total_cmp(&signed_area(&b.exterior)));
+        if let Some(owner) = owner {
+            owner.holes.push(hole);
+        }
+    }
+
+    polygons
+}
+
+impl Polygon {
+    pub fn area(&self) -> f64 {
+        signed_area(&self.exterior) + self.holes.iter().map(|hole| signed_area(hole)).sum::<f64>()
+    }
+
+    pub fn to_wkt(&self) -> String {
+        format!("POLYGON {}", self.wkt_rings())
+    }
+
+    pub fn to_geojson(&self) -> String {
+        format!("{{\"type\":\"Polygon\",\"coordinates\":{}}}", self.geojson_rings())
+    }
+
+    fn rings(&self) -> impl Iterator<Item = &Vec<[f32; 2]>> {
+        std::iter::once(&self.exterior).chain(self.holes.iter())
+    }
+
+    fn wkt_rings(&self) -> String {
+        let rings: Vec<String> = self
+            .rings()
+            .map(|ring| {
+                let mut text = String::from("(");
+                for [x, y] in ring.iter().chain(ring.first()) {
+                    if text.len() > 1 {
+                        text.push_str(", ");
+                    }
+                    let _ = write!(text, "{x} {y}");
+                }
+                text.push(')');
+                text
+            })
+            .collect();
+        format!("({})", rings.join(", "))
+    }
+
+    fn geojson_rings(&self) -> String {
+        let rings: Vec<String> = self
+            .rings()
+            .map(|ring| {
+                let coordinates: Vec<String> = ring
+                    .iter()
+                    .chain(ring.first())
+                    .map(|[x, y]| format!("[{x},{y}]"))
+                    .collect();
+                format!("[{}]", coordinates.join(","))
+            })
+            .collect();
+        format!("[{}]", rings.join(","))
+    }
+}
+
+pub fn to_wkt(polygons: &[Polygon]) -> String {
+    match polygons {
+        [] => String::from("POLYGON EMPTY"),
+        [polygon] => polygon.to_wkt(),
+        polygons => {
+            let parts: Vec<String> = polygons.iter().map(|polygon| polygon.wkt_rings()).collect();
+            format!("MULTIPOLYGON ({})", parts.join(", "))
+        }
+    }
+}
+
+// No polygons give a MultiPolygon without coordinates, which is valid GeoJSON for an empty
+// footprint like `POLYGON EMPTY` is for WKT.
+pub fn to_geojson(polygons: &[Polygon]) -> String {
+    match polygons {
+        [polygon] => polygon.to_geojson(),
+        polygons => {
+            let parts: Vec<String> = polygons.iter().map(|polygon| polygon.geojson_rings()).collect();
+            format!("{{\"type\":\"MultiPolygon\",\"coordinates\":[{}]}}", parts.join(","))
+        }
+    }
+}
+
+fn orient(a: &[f32; 2], b: &[f32; 2], c: &[f32; 2]) -> f64 {
+    let [ax, ay] = a.map(|value| value as f64);
+    let [bx, by] = b.map(|value| value as f64);
+    let [cx, cy] = c.map(|value| value as f64);
+    (bx - ax) * (cy - ay) - (by - ay) * (cx - ax)
+}
+
+fn signed_area(ring: &[[f32; 2]]) -> f64 {
+    let mut area = 0.0;
+    for (i, [x0, y0]) in ring.iter().enumerate() {
+        let [x1, y1] = ring[(i + 1) % ring.len()];
+        area += *x0 as f64 * y1 as f64 - x1 as f64 * *y0 as f64;
+    }
+    area / 2.0
+}
+
+fn contains(ring: &[[f32; 2]], point: &[f32; 2]) -> bool {
+    let mut inside = false;
+    let [px, py] = *point;
+    for (i, [x0, y0]) in ring.iter().enumerate() {
+        let [x1, y1] = ring[(i + 1) % ring.len()];
+        if (*y0 > py) != (y1 > py) && px < (x1 - x0) * (py - y0) / (y1 - y0) + x0 {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[derive(Debug, Clone)]
+struct Triangle {
+    // Counter-clockwise.
+    vertices: [usize; 3],
+    // `neighbours[i]` shares the edge opposite `vertices[i]`.
+    neighbours: [Option<usize>; 3],
+    alive: bool,
+}
+
+// Incremental Bowyer-Watson triangulation. Points are inserted in Morton order and located by
+// walking from the last inserted triangle, which keeps the cost close to linear for scans.
+struct Triangulation {
+    // Shifted to the bounding box minimum, followed by the three super triangle corners.
+    points: Vec<[f64; 2]>,
+    offset: [f64; 2],
+    triangles: Vec<Triangle>,
+    super_start: usize,
+}
+
+impl Triangulation {
+    fn new(input: &[[f32; 2]]) -> Self {
+        let mut min = [f64::INFINITY; 2];
+        let mut max = [f64::NEG_INFINITY; 2];
+        for point in input {
+            for axis in 0..2 {
+                min[axis] = min[axis].min(point[axis] as f64);
+                max[axis] = max[axis].max(point[axis] as f64);
+            }
+        }
+        if input.is_empty() {
+            min = [0.0; 2];
+            max = [0.0; 2];
+        }
+        let extent = (max[0] - min[0]).max(max[1] - min[1]).max(1.0);
+
+        let mut points: Vec<[f64; 2]> = input
+            .iter()
+            .map(|[x, y]| [*x as f64 - min[0], *y as f64 - min[1]])
+            .collect();
+        points.sort_by(|a, b| {
+            morton(a[0] / extent, a[1] / extent)
+                .cmp(&morton(b[0] / extent, b[1] / extent))
+                .then(a[0].total_cmp(&b[0]))
+                .then(a[1].total_cmp(&b[1]))
+        });
+        points.dedup();
+
+        let super_start = points.len();
+        let far = extent * 100.0;
+        points.push([-far, -far]);
+        points.push([far, -far]);
+        points.push([0.0, far]);
+
+        let mut triangulation = Self {
+            points,
+            offset: min,
+            triangles: vec![Triangle {
+                vertices: [super_start, super_start + 1, super_start + 2],
+                neighbours: [None, None, None],
+                alive: true,
+            }],
+            super_start,
+        };
+
+        let mut last = 0;
+        for i in 0..super_start {
+            last = triangulation.insert(i, last);
+        }
+        triangulation
+    }
+
+    fn original(&self, i: usize) -> [f32; 2] {
+        let [x, y] = self.points[i];
+        [(x + self.offset[0]) as f32, (y + self.offset[1]) as f32]
+    }
+
+    fn touches_super(&self, triangle: &Triangle) -> bool {
+        triangle.vertices.iter().any(|vertex| *vertex >= self.super_start)
+    }
+
+    fn circumradius(&self, triangle: &Triangle) -> f64 {
+        let [a, b, c] = triangle.vertices.map(|i| self.points[i]);
+        let ab = ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2)).sqrt();
+        let bc = ((c[0] - b[0]).powi(2) + (c[1] - b[1]).powi(2)).sqrt();
+        let ca = ((a[0] - c[0]).powi(2) + (a[1] - c[1]).powi(2)).sqrt();
+        let area = self.orient(triangle.vertices[0], triangle.vertices[1], triangle.vertices[2]).abs() / 2.0;
+        if area == 0.0 {
+            return f64::INFINITY;
+        }
+        ab * bc * ca / (4.0 * area)
+    }
+
+    fn orient(&self, a: usize, b: usize, c: usize) -> f64 {
+        let [a, b, c] = [a, b, c].map(|i| self.points[i]);
+        (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+    }
+
+    fn in_circumcircle(&self, triangle: usize, point: usize) -> bool {
+        let [a, b, c] = self.triangles[triangle].vertices.map(|i| self.points[i]);
+        let p = self.points[point];
+        let [ax, ay] = [a[0] - p[0], a[1] - p[1]];
+        let [bx, by] = [b[0] - p[0], b[1] - p[1]];
+        let [cx, cy] = [c[0] - p[0], c[1] - p[1]];
+        let determinant = (ax * ax + ay * ay) * (bx * cy - cx * by)
+            - (bx * bx + by * by) * (ax * cy - cx * ay)
+            + (cx * cx + cy * cy) * (ax * by - bx * ay);
+        determinant > 0.0
+    }
+
+    fn locate(&self, point: usize, start: usize) -> usize {
+        let mut current = if self.triangles[start].alive { start } else { self.any_alive() };
+        for _ in 0..self.triangles.len() {
+            let triangle = &self.triangles[current];
+            let mut moved = false;
+            for edge in 0..3 {
+                let a = triangle.vertices[(edge + 1) % 3];
+                let b = triangle.vertices[(edge + 2) % 3];
+                if self.orient(a, b, point) < 0.0 {
+                    if let Some(neighbour) = triangle.neighbours[edge] {
+                        current = neighbour;
+                        moved = true;
+                        break;
+                    }
+                }
+            }
+            if !moved {
+                return current;
+            }
+        }
+        current
+    }
+
+    fn any_alive(&self) -> usize {
+        self.triangles.iter().rposition(|triangle| triangle.alive).unwrap_or(0)
+    }
+
+    // Returns one of the new triangles as the start for the next walk.
+    fn insert(&mut self, point: usize, start: usize) -> usize {
+        let first = self.locate(point, start);
+
+        let mut cavity = vec![first];
+        let mut in_cavity = HashMap::from([(first, true)]);
+        let mut i = 0;
+        while i < cavity.len() {
+            let triangle = cavity[i];
+            for neighbour in self.triangles[triangle].neighbours.into_iter().flatten() {
+                if in_cavity.contains_key(&neighbour) {
+                    continue;
+                }
+                let inside = self.in_circumcircle(neighbour, point);
+                in_cavity.insert(neighbour, inside);
+                if inside {
+                    cavity.push(neighbour);
+                }
+            }
+            i += 1;
+        }
+
+        let mut by_start: HashMap<usize, usize> = HashMap::new();
+        let mut by_end: HashMap<usize, usize> = HashMap::new();
+        let mut created = Vec::new();
+
+        for triangle in cavity.iter() {
+            self.triangles[*triangle].alive = false;
+            let Triangle { vertices, neighbours, .. } = self.triangles[*triangle].clone();
+
+            for edge in 0..3 {
+                let outside = neighbours[edge];
+                if outside.is_some_and(|neighbour| in_cavity.get(&neighbour) == Some(&true)) {
+                    continue;
+                }
+
+                let a = vertices[(edge + 1) % 3];
+                let b = vertices[(edge + 2) % 3];
+                let new = self.triangles.len();
+                self.triangles.push(Triangle {
+                    vertices: [a, b, point],
+                    neighbours: [None, None, outside],
+                    alive: true,
+                });
+
+                if let Some(outside) = outside {
+                    for slot in self.triangles[outside].neighbours.iter_mut() {
+                        if *slot == Some(*triangle) {
+                            *slot = Some(new);
+                        }
+                    }
+                }
+
+                by_start.insert(a, new);
+                by_end.insert(b, new);
+                created.push(new);
+            }
+        }
+
+        for new in created.iter() {
+            let [a, b, _] = self.triangles[*new].vertices;
+            self.triangles[*new].neighbours[0] = by_start.get(&b).copied();
+            self.triangles[*new].neighbours[1] = by_end.get(&a).copied();
+        }
+
+        created.first().copied().unwrap_or(first)
+    }
+}
+
+fn morton(x: f64, y: f64) -> u64 {
+    fn spread(value: f64) -> u64 {
+        let mut v = (value.clamp(0.0, 1.0) * 65535.0) as u64;
+        v = (v | (v << 8)) & 0x00ff00ff;
+        v = (v | (v << 4)) & 0x0f0f0f0f;
+        v = (v | (v << 2)) & 0x33333333;
+        v = (v | (v << 1)) & 0x55555555;
+        v
+    }
+    spread(x) | (spread(y) << 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{concave_hull, convex_hull, signed_area, to_geojson, XZ};
+    use crate::{LooseInsertion, Octree, SphereMode};
+
+    // Unit grid over a 10 by 10 square with a 4 by 4 hole in the middle.
+    fn square_with_hole() -> Vec<[f32; 2]> {
+        let mut points = Vec::new();
+        for x in 0..=10 {
+            for y in 0..=10 {
+                if !((4..=6).contains(&x) && (4..=6).contains(&y)) {
+                    points.push([x as f32, y as f32]);
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn convex_hull_is_the_outer_square() {
+        let hull = convex_hull(&square_with_hole()).unwrap();
+        let mut corners = hull.exterior.clone();
+        corners.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+        assert_eq!(corners, vec![[0.0, 0.0], [0.0, 10.0], [10.0, 0.0], [10.0, 10.0]]);
+        assert!(hull.holes.is_empty());
+        assert!(signed_area(&hull.exterior) > 0.0);
+        assert_eq!(hull.area(), 100.0);
+    }
+
+    #[test]
+    fn alpha_hull_keeps_the_hole() {
+        // Grid cells have a circumradius of about 0.71, triangles across the hole at least 2.
+        let polygons = concave_hull(&square_with_hole(), 0.75);
+        assert_eq!(polygons.len(), 1);
+        assert_eq!(polygons[0].holes.len(), 1);
+        assert!(signed_area(&polygons[0].holes[0]) < 0.0);
+        // The corner cells of the hole keep the halves without a removed point, so the hole is an
+        // octagon of 16 - 4 * 0.5.
+        assert!((polygons[0].area() - 86.0).abs() < 1e-6, "{}", polygons[0].area());
+    }
+
+    #[test]
+    fn large_alpha_fills_the_hole() {
+        let polygons = concave_hull(&square_with_hole(), 100.0);
+        assert_eq!(polygons.len(), 1);
+        assert!(polygons[0].holes.is_empty());
+        assert!((polygons[0].area() - 100.0).abs() < 1e-6, "{}", polygons[0].area());
+    }
+
+    #[test]
+    fn octree_footprint_outlines_its_points() {
+        // The height goes on the second axis, like las_viewer imports points.
+        let points: Vec<[f32; 3]> = square_with_hole().iter().map(|[x, y]| [*x * 0.5 + 1.0, 3.0, *y * 0.5 + 2.0]).collect();
+        let mut tree = Octree::builder(10.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        tree.import(&points);
+        assert_eq!(tree.point_count(), points.len());
+
+        let convex = tree.footprint(XZ, None);
+        assert_eq!(convex.len(), 1);
+        assert!((convex[0].area() - 25.0).abs() < 1e-6, "{}", convex[0].area());
+
+        let concave = tree.footprint(XZ, Some(0.375));
+        assert_eq!(concave.len(), 1);
+        assert_eq!(concave[0].holes.len(), 1);
+        assert!((concave[0].area() - 21.5).abs() < 1e-6, "{}", concave[0].area());
+
+        assert!(Octree::new(10.0).footprint(XZ, None).is_empty());
+    }
+
+    #[test]
+    fn empty_geojson_is_an_empty_multipolygon() {
+        assert_eq!(to_geojson(&[]), "{\"type\":\"MultiPolygon\",\"coordinates\":[]}");
+    }
+}
@@ -0,0 +1,350 @@
+use std::collections::HashMap;
+
+use nalgebra::{Matrix3, Matrix4, Matrix6, Rotation3, Vector3, Vector6};
+
+use crate::{distance_squared, estimate_normal, Octree};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IcpMethod {
+    PointToPoint,
+    PointToPlane,
+}
+
+#[derive(Debug, Clone)]
+pub struct IcpParams {
+    pub method: IcpMethod,
+    pub max_iterations: usize,
+    // Stop once the rms error changes by less than this between iterations.
+    pub tolerance: f32,
+    // Pairs further apart than this are ignored, `None` keeps every pair.
+    pub max_correspondence_distance: Option<f32>,
+    // Neighbours used to estimate target normals for `IcpMethod::PointToPlane`.
+    pub normal_neighbours: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct IcpResult {
+    // Row-major rigid transform that maps source points onto the target.
+    pub transform: [[f32; 4]; 4],
+    pub rms_error: f32,
+    pub iterations: usize,
+    // Whether the rms error settled within `IcpParams::tolerance` before `max_iterations` ran out
+    // or too few pairs were left to solve for a step.
+    pub converged: bool,
+}
+
+// Target normals by the bits of their point, `None` where they cannot be estimated.
+type NormalCache = HashMap<[u32; 3], Option<Vector3<f64>>>;
+
+struct Correspondence {
+    source: Vector3<f64>,
+    target: Vector3<f64>,
+    normal: Vector3<f64>,
+}
+
+impl Default for IcpParams {
+    fn default() -> Self {
+        Self {
+            method: IcpMethod::PointToPoint,
+            max_iterations: 50,
+            tolerance: 1e-6,
+            max_correspondence_distance: None,
+            normal_neighbours: 8,
+        }
+    }
+}
+
+pub const IDENTITY: [[f32; 4]; 4] = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+// Aligns `source` to the points stored in `target`, starting from `initial`.
+pub fn align(source: &[[f32; 3]], target: &Octree, initial: [[f32; 4]; 4], params: &IcpParams) -> IcpResult {
+    let mut transform = Matrix4::from_fn(|row, col| initial[row][col] as f64);
+    // Target points keep their normals across iterations, each is estimated once.
+    let mut normals = NormalCache::new();
+
+    let mut correspondences = find_correspondences(source, target, &transform, params, &mut normals);
+    let mut rms_error = rms(&correspondences, params.method);
+    let mut iterations = 0;
+    let mut converged = false;
+
+    while iterations < params.max_iterations {
+        let step = match params.method {
+            IcpMethod::PointToPoint => solve_point_to_point(&correspondences),
+            IcpMethod::PointToPlane => solve_point_to_plane(&correspondences),
+        };
+        let Some(step) = step else {
+            break;
+        };
+
+        transform = step * transform;
+        iterations += 1;
+
+        correspondences = find_correspondences(source, target, &transform, params, &mut normals);
+        let new_rms_error = rms(&correspondences, params.method);
+        converged = (rms_error - new_rms_error).abs() < params.tolerance as f64;
+        rms_error = new_rms_error;
+
+        if converged {
+            break;
+        }
+    }
+
+    IcpResult {
+        transform: std::array::from_fn(|row| std::array::from_fn(|col| transform[(row, col)] as f32)),
+        rms_error: rms_error as f32,
+        iterations,
+        converged,
+    }
+}
+
+pub fn transform_point(transform: &[[f32; 4]; 4], point: &[f32; 3]) -> [f32; 3] {
+    let [x, y, z] = *point;
+    std::array::from_fn(|row| {
+        transform[row][0] * x + transform[row][1] * y + transform[row][2] * z + transform[row][3]
+    })
+}
+
+fn find_correspondences(
+    source: &[[f32; 3]],
+    target: &Octree,
+    transform: &Matrix4<f64>,
+    params: &IcpParams,
+    normals: &mut NormalCache,
+) -> Vec<Correspondence> {
+    let max_distance_squared = params
+        .max_correspondence_distance
+        .map_or(f32::INFINITY, |distance| distance * distance);
+
+    let mut correspondences = Vec::with_capacity(source.len());
+    for point in source {
+        let moved = transform.transform_point(&nalgebra::Point3::new(point[0] as f64, point[1] as f64, point[2] as f64));
+        let moved_f32 = [moved.x as f32, moved.y as f32, moved.z as f32];
+
+        let Some(nearest) = target.nearest(&moved_f32) else {
+            continue;
+        };
+        if distance_squared(&nearest, &moved_f32) > max_distance_squared {
+            continue;
+        }
+
+        let normal = match params.method {
+            IcpMethod::PointToPoint => Vector3::zeros(),
+            IcpMethod::PointToPlane => {
+                let normal = normals.entry(nearest.map(f32::to_bits)).or_insert_with(|| {
+                    let neighbours = target.k_nearest(&nearest, params.normal_neighbours.max(3));
+                    estimate_normal(&neighbours).map(|[nx, ny, nz]| Vector3::new(nx as f64, ny as f64, nz as f64))
+                });
+                let Some(normal) = *normal else {
+                    continue;
+                };
+                normal
+            }
+        };
+
+        correspondences.push(Correspondence {
+            source: moved.coords,
+            target: Vector3::new(nearest[0] as f64, nearest[1] as f64, nearest[2] as f64),
+            normal,
+        });
+    }
+    correspondences
+}
+
+fn rms(correspondences: &[Correspondence], method: IcpMethod) -> f64 {
+    if correspondences.is_empty() {
+        return f64::INFINITY;
+    }
+
+    let sum: f64 = correspondences
+        .iter()
+        .map(|pair| match method {
+            IcpMethod::PointToPoint => (pair.target - pair.source).norm_squared(),
+            IcpMethod::PointToPlane => (pair.target - pair.source).dot(&pair.normal).powi(2),
+        })
+        .sum();
+    (sum / correspondences.len() as f64).sqrt()
+}
+
+fn solve_point_to_point(correspondences: &[Correspondence]) -> Option<Matrix4<f64>> {
+    if correspondences.len() < 3 {
+        return None;
+    }
+
+    let count = correspondences.len() as f64;
+    let source_centroid = correspondences.iter().map(|pair| pair.source).sum::<Vector3<f64>>() / count;
+    let target_centroid = correspondences.iter().map(|pair| pair.target).sum::<Vector3<f64>>() / count;
+
+    let mut covariance = Matrix3::zeros();
+    for pair in correspondences {
+        covariance += (pair.source - source_centroid) * (pair.target - target_centroid).transpose();
+    }
+
+    let svd = covariance.svd(true, true);
+    let u = svd.u?;
+    let mut v = svd.v_t?.transpose();
+    let mut rotation = v * u.transpose();
+    if rotation.determinant() < 0.0 {
+        v.column_mut(2).neg_mut();
+        rotation = v * u.transpose();
+    }
+    let translation = target_centroid - rotation * source_centroid;
+
+    let mut step = Matrix4::identity();
+    step.fixed_view_mut::<3, 3>(0, 0).copy_from(&rotation);
+    step.fixed_view_mut::<3, 1>(0, 3).copy_from(&translation);
+    Some(step)
+}
+
+// Linearised least squares over small rotations, see Low (2004).
+fn solve_point_to_plane(correspondences: &[Correspondence]) -> Option<Matrix4<f64>> {
+    if correspondences.len() < 6 {
+        return None;
+    }
+
+    let mut ata = Matrix6::zeros();
+    let mut atb = Vector6::zeros();
+    for pair in correspondences {
+        let cross = pair.source.cross(&pair.normal);
+        let row = Vector6::new(cross.x, cross.y, cross.z, pair.normal.x, pair.normal.y, pair.normal.z);
+        let residual = (pair.target - pair.source).dot(&pair.normal);
+        ata += row * row.transpose();
+        atb += row * residual;
+    }
+
+    let x = ata.cholesky()?.solve(&atb);
+    let rotation = Rotation3::new(Vector3::new(x[0], x[1], x[2]));
+
+    let mut step = Matrix4::identity();
+    step.fixed_view_mut::<3, 3>(0, 0).copy_from(rotation.matrix());
+    step.fixed_view_mut::<3, 1>(0, 3).copy_from(&Vector3::new(x[3], x[4], x[5]));
+    Some(step)
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::{Matrix4, Rotation3, Vector3};
+
+    use super::{align, transform_point, IcpMethod, IcpParams, IDENTITY};
+    use crate::{LooseInsertion, Octree, SphereMode};
+
+    // Uneven height field, so no sliding along it keeps the points on it.
+    fn surface() -> Vec<[f32; 3]> {
+        let mut points = Vec::new();
+        for i in 0..40 {
+            for j in 0..40 {
+                let (x, z) = (2.0 + i as f32 * 0.15, 2.0 + j as f32 * 0.15);
+                points.push([x, 5.0 + 0.5 * (1.3 * x).sin() * (0.9 * z).cos() + 0.1 * x, z]);
+            }
+        }
+        points
+    }
+
+    // Source points that `expected` maps onto the surface. No point moves by half the grid spacing,
+    // so nearest neighbours start out as the right pairs.
+    fn moved_surface() -> (Vec<[f32; 3]>, Matrix4<f64>) {
+        let rotation = Rotation3::from_euler_angles(0.005, 0.01, -0.005);
+        let expected = Matrix4::new_translation(&Vector3::new(0.02, -0.01, 0.015)) * rotation.to_homogeneous();
+        let inverse = expected.try_inverse().unwrap();
+        let source = surface()
+            .iter()
+            .map(|point| {
+                let moved = inverse.transform_point(&nalgebra::Point3::new(point[0] as f64, point[1] as f64, point[2] as f64));
+                [moved.x as f32, moved.y as f32, moved.z as f32]
+            })
+            .collect();
+        (source, expected)
+    }
+
+    #[test]
+    fn recovers_a_rigid_transform() {
+        let mut target = Octree::builder(10.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        target.import(&surface());
+        let (source, expected) = moved_surface();
+
+        for method in [IcpMethod::PointToPoint, IcpMethod::PointToPlane] {
+            let params = IcpParams { method, ..IcpParams::default() };
+            let result = align(&source, &target, IDENTITY, &params);
+            assert!(result.converged, "{method:?}: {result:?}");
+            assert!(result.rms_error < 1e-3, "{method:?}: {result:?}");
+            for (row, values) in result.transform.iter().enumerate() {
+                for (col, value) in values.iter().enumerate() {
+                    assert!((*value as f64 - expected[(row, col)]).abs() < 1e-3, "{method:?}: {result:?}");
+                }
+            }
+
+            let moved = transform_point(&result.transform, &source[0]);
+            let [x, y, z] = surface()[0];
+            assert!((moved[0] - x).abs() < 1e-3 && (moved[1] - y).abs() < 1e-3 && (moved[2] - z).abs() < 1e-3);
+        }
+    }
+
+    // Starts a tenth of a unit above the surface, point-to-plane pairs pull the source back along
+    // the surface normals.
+    #[test]
+    fn point_to_plane_recovers_a_transform_from_further_off() {
+        let mut target = Octree::builder(10.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        target.import(&surface());
+        let (source, expected) = moved_surface();
+        let offset = Matrix4::new_translation(&Vector3::new(0.0, 0.1, 0.0));
+        let initial = std::array::from_fn(|row| std::array::from_fn(|col| offset[(row, col)] as f32));
+
+        let params = IcpParams { method: IcpMethod::PointToPlane, ..IcpParams::default() };
+        let result = align(&source, &target, initial, &params);
+        assert!(result.converged, "{result:?}");
+        assert!(result.rms_error < 1e-4, "{result:?}");
+        for (row, values) in result.transform.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                assert!((*value as f64 - expected[(row, col)]).abs() < 1e-3, "{result:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn reports_when_it_does_not_converge() {
+        let mut target = Octree::builder(10.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        target.import(&surface());
+        let (source, _) = moved_surface();
+
+        let params = IcpParams { max_iterations: 1, ..IcpParams::default() };
+        let result = align(&source, &target, IDENTITY, &params);
+        assert_eq!(result.iterations, 1);
+        assert!(!result.converged, "{result:?}");
+    }
+
+    #[test]
+    fn keeps_the_initial_transform_with_too_few_points() {
+        let mut target = Octree::builder(10.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        target.import(&surface());
+        let source = &surface()[..2];
+
+        for (method, count) in [(IcpMethod::PointToPoint, 2), (IcpMethod::PointToPlane, 5)] {
+            let source = &surface()[..count];
+            let params = IcpParams { method, ..IcpParams::default() };
+            let result = align(source, &target, IDENTITY, &params);
+            assert_eq!(result.iterations, 0, "{method:?}");
+            assert!(!result.converged, "{method:?}");
+            assert_eq!(result.transform, IDENTITY, "{method:?}");
+        }
+
+        let result = align(source, &Octree::new(10.0), IDENTITY, &IcpParams::default());
+        assert_eq!(result.iterations, 0);
+        assert!(result.rms_error.is_infinite());
+    }
+}
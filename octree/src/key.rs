@@ -0,0 +1,185 @@
+use std::fmt;
+
+// Offsets of the children in the order `octree_divide_into_cube` creates them.
+pub const CHILD_OFFSETS: [[u32; 3]; 8] = [
+    [0, 0, 0],
+    [0, 1, 0],
+    [1, 1, 0],
+    [1, 0, 0],
+    [0, 0, 1],
+    [0, 1, 1],
+    [1, 1, 1],
+    [1, 0, 1],
+];
+
+// Integer address of a node, `x`, `y` and `z` count cells of the node's size from the root
+// origin. The path form is `r` followed by one child index per level, e.g. `r0473`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeKey {
+    pub depth: u8,
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl NodeKey {
+    pub const ROOT: NodeKey = NodeKey { depth: 0, x: 0, y: 0, z: 0 };
+    pub const MAX_DEPTH: u8 = 31;
+
+    pub fn new(depth: u8, x: u32, y: u32, z: u32) -> Self {
+        Self { depth, x, y, z }
+    }
+
+    pub fn child(&self, i: usize) -> Self {
+        let [dx, dy, dz] = CHILD_OFFSETS[i];
+        Self {
+            depth: self.depth + 1,
+            x: self.x * 2 + dx,
+            y: self.y * 2 + dy,
+            z: self.z * 2 + dz,
+        }
+    }
+
+    pub fn children(&self) -> [Self; 8] {
+        std::array::from_fn(|i| self.child(i))
+    }
+
+    pub fn parent(&self) -> Option<Self> {
+        if self.depth == 0 {
+            return None;
+        }
+
+        Some(Self {
+            depth: self.depth - 1,
+            x: self.x / 2,
+            y: self.y / 2,
+            z: self.z / 2,
+        })
+    }
+
+    // Index of this node in its parent's `nodes`.
+    pub fn child_index(&self) -> Option<usize> {
+        if self.depth == 0 {
+            return None;
+        }
+
+        let offset = [self.x & 1, self.y & 1, self.z & 1];
+        CHILD_OFFSETS.iter().position(|child_offset| *child_offset == offset)
+    }
+
+    // Child indices from the root down to this node.
+    pub fn child_indices(&self) -> Vec<usize> {
+        let mut indices = Vec::with_capacity(self.depth as usize);
+        let mut key = *self;
+        while let (Some(i), Some(parent)) = (key.child_index(), key.parent()) {
+            indices.push(i);
+            key = parent;
+        }
+        indices.reverse();
+        indices
+    }
+
+    // Key of this node once the root it is relative to becomes the node at `prefix`, as
+    // `Octree::grow_to_fit` does.
+    pub fn moved_under(&self, prefix: &NodeKey) -> Self {
+        let shift = self.depth;
+        Self {
+            depth: prefix.depth + self.depth,
+            x: prefix.x << shift | self.x,
+            y: prefix.y << shift | self.y,
+            z: prefix.z << shift | self.z,
+        }
+    }
+
+    pub fn is_ancestor_of(&self, other: &NodeKey) -> bool {
+        if self.depth >= other.depth {
+            return false;
+        }
+
+        let shift = other.depth - self.depth;
+        other.x >> shift == self.x && other.y >> shift == self.y && other.z >> shift == self.z
+    }
+
+    pub fn path(&self) -> String {
+        let mut path = String::with_capacity(self.depth as usize + 1);
+        path.push('r');
+        for i in self.child_indices() {
+            path.push(char::from(b'0' + i as u8));
+        }
+        path
+    }
+
+    pub fn from_path(path: &str) -> Option<Self> {
+        let digits = path.strip_prefix('r')?;
+        if digits.len() > Self::MAX_DEPTH as usize {
+            return None;
+        }
+
+        let mut key = Self::ROOT;
+        for digit in digits.chars() {
+            let i = digit.to_digit(10)? as usize;
+            if i >= 8 {
+                return None;
+            }
+            key = key.child(i);
+        }
+        Some(key)
+    }
+}
+
+impl fmt::Display for NodeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NodeKey;
+
+    #[test]
+    fn children_round_trip_through_their_parent() {
+        let keys = [NodeKey::ROOT, NodeKey::new(1, 1, 0, 1), NodeKey::new(5, 17, 3, 30)];
+        for key in keys {
+            for (i, child) in key.children().into_iter().enumerate() {
+                assert_eq!(child.depth, key.depth + 1);
+                assert_eq!(child.parent(), Some(key));
+                assert_eq!(child.child_index(), Some(i));
+                assert!(key.is_ancestor_of(&child));
+                assert!(!child.is_ancestor_of(&key));
+            }
+        }
+        assert_eq!(NodeKey::ROOT.parent(), None);
+        assert_eq!(NodeKey::ROOT.child_index(), None);
+    }
+
+    #[test]
+    fn paths_round_trip() {
+        let key = NodeKey::from_path("r0473").unwrap();
+        assert_eq!(key, NodeKey::ROOT.child(0).child(4).child(7).child(3));
+        assert_eq!(key.child_indices(), vec![0, 4, 7, 3]);
+        assert_eq!(key.path(), "r0473");
+        assert_eq!(key.to_string(), "r0473");
+        assert_eq!(NodeKey::from_path("r"), Some(NodeKey::ROOT));
+        assert_eq!(NodeKey::ROOT.path(), "r");
+    }
+
+    #[test]
+    fn invalid_paths_are_rejected() {
+        assert_eq!(NodeKey::from_path(""), None);
+        assert_eq!(NodeKey::from_path("0473"), None);
+        assert_eq!(NodeKey::from_path("r048"), None);
+        assert_eq!(NodeKey::from_path("r04a"), None);
+        let too_deep = format!("r{}", "1".repeat(NodeKey::MAX_DEPTH as usize + 1));
+        assert_eq!(NodeKey::from_path(&too_deep), None);
+    }
+
+    #[test]
+    fn moved_under_prefixes_the_path() {
+        let key = NodeKey::from_path("r473").unwrap();
+        let prefix = NodeKey::from_path("r06").unwrap();
+        assert_eq!(key.moved_under(&prefix).path(), "r06473");
+        assert_eq!(key.moved_under(&NodeKey::ROOT), key);
+        assert_eq!(NodeKey::ROOT.moved_under(&prefix), prefix);
+    }
+}
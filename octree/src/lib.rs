@@ -1,14 +1,36 @@
 use std::f32::consts::PI;
 
+pub mod aggregates;
+mod attributes;
+pub mod builder;
+pub mod dedup;
+mod error;
+pub mod footprint;
+pub mod icp;
+pub mod key;
+pub mod neighbours;
+pub mod reconstruction;
+pub mod segmentation;
+pub mod validate;
+
+pub use aggregates::Aggregates;
+pub use attributes::PointAttributes;
+pub use builder::{Division, Layout, LooseInsertion, NodeKind, OctreeBuilder, SphereMode};
+pub use dedup::DuplicateWinner;
+pub use error::OctreeError;
+pub use key::NodeKey;
+
 #[derive(Debug, Clone)]
 pub struct Octree {
     pub root: OctreeNode, 
+    pub layout: Layout,
 }
 
 #[derive(Debug, Clone)]
 pub struct OctreeCube {
     pub cube_points: [[f32; 3]; 8],
     pub nodes: Option<Box<[OctreeNode; 8]>>,
+    pub aggregates: Box<Aggregates>,
 }
 
 #[derive(Debug, Clone)]
@@ -16,50 +38,269 @@ pub struct OctreeSphere {
     pub middle: [f32; 3],
     pub radius: f32,
     pub data_points: Vec<[f32; 3]>,
+    // Empty when the points were imported without attributes, otherwise one per data point.
+    pub attributes: Vec<PointAttributes>,
     pub cube_points: [[f32; 3]; 8],
     pub sphere_points: [[f32; 3]; 12],
     pub nodes: Option<Box<[OctreeNode; 8]>>,
+    pub aggregates: Box<Aggregates>,
 }
 
 #[derive(Debug, Clone)]
 pub enum OctreeNode {
     Cube(OctreeCube),
-    Sphere(OctreeSphere),
+    // Boxed, spheres carry their points and hull and are much larger than cubes.
+    Sphere(Box<OctreeSphere>),
 }
 
 impl Octree {
     pub fn new(size: f32) -> Self {
         let root = gen_cube(0.0, 0.0, 0.0, size);
+        let layout = Layout::default();
 
-        
-        let mut nodes = OctreeCube::into_cluster(root);
-        for node in nodes.iter_mut() {
-            node.divide_into_spheres();
-            for node in node.nodes_mut().unwrap().iter_mut() {
-                node.divide_into_spheres();
+        Self {
+            root: layout.gen_node(root, 0),
+            layout,
+        }
+    }
+
+    // Same layout as `new`, but rejects sizes that are zero, negative, NaN or infinite.
+    pub fn try_new(size: f32) -> Result<Self, OctreeError> {
+        OctreeBuilder::new(size).build()
+    }
+
+    pub fn builder(size: f32) -> OctreeBuilder {
+        OctreeBuilder::new(size)
+    }
+
+    pub fn bounds(&self) -> [[f32; 3]; 2] {
+        self.root.bounds()
+    }
+
+    pub fn get(&self, key: &NodeKey) -> Option<&OctreeNode> {
+        let mut node = &self.root;
+        for i in key.child_indices() {
+            node = node.child(i)?;
+        }
+        Some(node)
+    }
+
+    pub fn get_mut(&mut self, key: &NodeKey) -> Option<&mut OctreeNode> {
+        let mut node = &mut self.root;
+        for i in key.child_indices() {
+            node = node.child_mut(i)?;
+        }
+        Some(node)
+    }
+
+    // Visits every node parents first, together with its key.
+    pub fn for_each_node(&self, mut f: impl FnMut(NodeKey, &OctreeNode)) {
+        let mut stack = vec![(NodeKey::ROOT, &self.root)];
+        while let Some((key, node)) = stack.pop() {
+            f(key, node);
+            if let Some(nodes) = node.nodes_ref() {
+                for (i, child) in nodes.iter().enumerate().rev() {
+                    stack.push((key.child(i), child));
+                }
             }
         }
+    }
 
-        Self {
-            root: OctreeNode::Cube(
-                OctreeCube {
-                    cube_points: root,
-                    nodes: Some(Box::new(nodes)),
+    // Key of the leaf whose cell holds `point`, points outside of the root go to the closest one.
+    // In loose trees every point is stored there, copies may be stored in neighbours as well.
+    pub fn leaf_key(&self, point: &[f32; 3]) -> NodeKey {
+        let mut key = NodeKey::ROOT;
+        let mut node = &self.root;
+        while let Some(nodes) = node.nodes_ref() {
+            let i = child_towards(nodes, point);
+            key = key.child(i);
+            node = &nodes[i];
+        }
+        key
+    }
+
+    // Doubles the root until every point lies inside it, the old root becomes one of the children
+    // and its new siblings start out empty. Returns the key the old root has now, see
+    // `NodeKey::moved_under`, the root key when the tree did not grow. Fails, leaving the tree as
+    // it is, when growing would make the tree deeper than `NodeKey::MAX_DEPTH`.
+    pub fn grow_to_fit(&mut self, data: &[[f32; 3]]) -> Result<NodeKey, OctreeError> {
+        let mut old_root_key = NodeKey::ROOT;
+        let Some([min, max]) = points_bounds(data) else {
+            return Ok(old_root_key);
+        };
+        if !min.iter().chain(max.iter()).all(|value| value.is_finite()) {
+            return Ok(old_root_key);
+        }
+
+        // Every doubling is worked out first, so a tree that cannot grow enough is not touched.
+        let mut layout = self.layout.clone();
+        let mut roots = Vec::new();
+        let [mut root_min, mut root_max] = self.bounds();
+        while !(0..3).all(|axis| root_min[axis] <= min[axis] && max[axis] <= root_max[axis]) {
+            layout.grow()?;
+            let size = root_max[0] - root_min[0];
+            let origin: [f32; 3] = std::array::from_fn(|axis| {
+                if min[axis] <= root_min[axis] {
+                    root_min[axis] - size
+                } else {
+                    root_min[axis]
+                }
+            });
+            let grown = gen_cube(origin[0], origin[1], origin[2], size * 2.0);
+            [root_min, root_max] = [grown[0], grown[6]];
+            roots.push(grown);
+        }
+
+        for grown in roots {
+            let old_root_min = self.bounds()[0];
+            let cubes = octree_divide_into_cube(&grown);
+            let old_index = (0..8)
+                .min_by(|a, b| distance_squared(&cubes[*a][0], &old_root_min).total_cmp(&distance_squared(&cubes[*b][0], &old_root_min)))
+                .unwrap();
+
+            self.layout.grow()?;
+            let sphere_mode = self.layout.sphere_mode;
+            let mut old_root = Some(std::mem::replace(&mut self.root, self.layout.kind(0).node(grown, sphere_mode)));
+            let nodes = std::array::from_fn(|i| {
+                if i == old_index {
+                    old_root.take().unwrap()
+                } else {
+                    self.layout.kind(1).node(cubes[i], sphere_mode)
                 }
-            ),
+            });
+
+            self.root.set_nodes(nodes);
+            self.root.refresh_aggregates();
+            old_root_key = old_root_key.moved_under(&NodeKey::ROOT.child(old_index));
         }
+        Ok(old_root_key)
+    }
+
+    // Moves every point of `other` into `self`, growing the root when `other` reaches outside of
+    // it. Growing moves the nodes of `self` down, keys taken before the merge address the same
+    // nodes after `NodeKey::moved_under` the returned key. Fails like `grow_to_fit`, then `self`
+    // is not changed.
+    pub fn merge(&mut self, other: Octree) -> Result<NodeKey, OctreeError> {
+        let (data, attributes) = other.into_points_with_attributes();
+        let old_root_key = self.grow_to_fit(&data)?;
+        self.root.import_routed(&data, &attributes, 0, &self.layout);
+        Ok(old_root_key)
+    }
+
+    // Points of `self` with no point of `other` within `tolerance`.
+    pub fn diff(&self, other: &Octree, tolerance: f32) -> Vec<[f32; 3]> {
+        let tolerance_squared = tolerance * tolerance;
+        self.points()
+            .copied()
+            .filter(|data_point| {
+                other
+                    .nearest(data_point)
+                    .is_none_or(|nearest| distance_squared(&nearest, data_point) > tolerance_squared)
+            })
+            .collect()
+    }
+
+    // Points only in `self` and points only in `other`, see `diff`.
+    pub fn symmetric_diff(&self, other: &Octree, tolerance: f32) -> (Vec<[f32; 3]>, Vec<[f32; 3]>) {
+        (self.diff(other, tolerance), other.diff(self, tolerance))
     }
 
     pub fn import(&mut self, data: &[[f32; 3]]) {
-        self.root.import(data);
-        
+        self.root.import_routed(data, &[], 0, &self.layout);
+    }
+
+    // Nothing is imported unless every point has its attributes.
+    pub fn import_with_attributes(&mut self, data: &[[f32; 3]], attributes: &[PointAttributes]) -> Result<(), OctreeError> {
+        if data.len() != attributes.len() {
+            return Err(OctreeError::AttributeCountMismatch { points: data.len(), attributes: attributes.len() });
+        }
+
+        self.root.import_routed(data, attributes, 0, &self.layout);
+        Ok(())
+    }
+
+    // Like `import`, but nothing is imported when any coordinate is NaN or infinite.
+    pub fn try_import(&mut self, data: &[[f32; 3]]) -> Result<(), OctreeError> {
+        if let Some(index) = data.iter().position(|data_point| !data_point.iter().all(|value| value.is_finite())) {
+            return Err(OctreeError::NonFinitePoint { index, point: data[index] });
+        }
+
+        self.import(data);
+        Ok(())
     }
 
     pub fn export(&self) -> Vec<[f32; 3]> {
-        let mut output = Vec::new();
+        let mut output = Vec::with_capacity(self.point_count());
         self.root.export(&mut output);
         output
     }
+
+    // Attributes in the same order as `export`, points imported without any get default ones.
+    pub fn export_attributes(&self) -> Vec<PointAttributes> {
+        let mut output = Vec::with_capacity(self.point_count());
+        self.for_each_node(|_, node| {
+            if node.attributes().is_empty() {
+                output.resize(output.len() + node.data_points().len(), PointAttributes::default());
+            } else {
+                output.extend_from_slice(node.attributes());
+            }
+        });
+        output
+    }
+
+    // Data points of every node that holds any, in the same order as `export`.
+    pub fn leaves(&self) -> Leaves<'_> {
+        Leaves { stack: vec![&self.root] }
+    }
+
+    pub fn points(&self) -> impl Iterator<Item = &[f32; 3]> + '_ {
+        self.leaves().flatten()
+    }
+
+    pub fn point_count(&self) -> usize {
+        self.leaves().map(|data_points| data_points.len()).sum()
+    }
+
+    // Moves the points out of the tree, leaves are freed as soon as they are copied.
+    pub fn into_points(self) -> Vec<[f32; 3]> {
+        let mut output = Vec::with_capacity(self.point_count());
+        self.root.into_points(&mut output);
+        output
+    }
+
+    // Like `into_points`, together with the attributes in the same order as `export_attributes`.
+    // The attributes are empty when no point was imported with any.
+    pub fn into_points_with_attributes(self) -> (Vec<[f32; 3]>, Vec<PointAttributes>) {
+        let mut points = Vec::with_capacity(self.point_count());
+        let mut attributes = Vec::new();
+        self.root.into_points_with_attributes(&mut points, &mut attributes);
+        if !attributes.is_empty() {
+            attributes.resize(points.len(), PointAttributes::default());
+        }
+        (points, attributes)
+    }
+
+    pub fn nearest(&self, point: &[f32; 3]) -> Option<[f32; 3]> {
+        let mut best = None;
+        self.root.nearest(point, self.layout.sphere_mode.margin(), &mut best);
+        best.map(|(data_point, _)| data_point)
+    }
+
+    pub fn k_nearest(&self, point: &[f32; 3], k: usize) -> Vec<[f32; 3]> {
+        let mut best = Vec::with_capacity(k + 1);
+        if k > 0 {
+            self.root.k_nearest(point, k, self.layout.sphere_mode.margin(), &mut best);
+        }
+        best.into_iter().map(|(data_point, _)| data_point).collect()
+    }
+
+    // Normals of `points` from their `k` nearest neighbours in the tree, zero where undetermined.
+    pub fn normals(&self, points: &[[f32; 3]], k: usize) -> Vec<[f32; 3]> {
+        points
+            .iter()
+            .map(|point| estimate_normal(&self.k_nearest(point, k)).unwrap_or([0.0, 0.0, 0.0]))
+            .collect()
+    }
 }
 
 impl OctreeNode {
@@ -88,24 +329,31 @@ impl OctreeNode {
         self.set_nodes(cluster);
     }
 
+    pub fn try_node(&mut self, i: usize) -> Result<&mut OctreeNode, OctreeError> {
+        let nodes = self.nodes_mut().ok_or(OctreeError::NotDivided)?;
+        nodes.get_mut(i).ok_or(OctreeError::ChildIndexOutOfRange(i))
+    }
+
+    // Panics when the node has no children or `i` is not below 8, `try_node` reports those.
     pub fn node(&mut self, i: usize) -> &mut OctreeNode {
-        match self {
-            OctreeNode::Cube(cube) => {
-                &mut cube.nodes.as_mut().unwrap()[i]
-            }
-            OctreeNode::Sphere(sphere) => {
-                &mut  sphere.nodes.as_mut().unwrap()[i]
-            }
-        }
+        self.try_node(i).unwrap()
+    }
+
+    pub fn child(&self, i: usize) -> Option<&OctreeNode> {
+        self.nodes_ref()?.get(i)
+    }
+
+    pub fn child_mut(&mut self, i: usize) -> Option<&mut OctreeNode> {
+        self.nodes_mut()?.get_mut(i)
     }
 
     pub fn cube_points(&self) -> [[f32; 3]; 8] {
         match self {
             OctreeNode::Cube(cube) => {
-                cube.cube_points.clone()
+                cube.cube_points
             }
             OctreeNode::Sphere(sphere) => {
-                sphere.cube_points.clone()
+                sphere.cube_points
             }
         }
     }
@@ -145,17 +393,27 @@ impl OctreeNode {
 
     pub fn add_data_point(&mut self, data_point: [f32; 3]) {
         match self {
-            OctreeNode::Cube(cube) => {
+            OctreeNode::Cube(_) => {
+            }
+            OctreeNode::Sphere(sphere) => {
+                sphere.add_data_point(data_point, None);
+            }
+        }
+    }
+
+    pub fn add_data_point_with_attributes(&mut self, data_point: [f32; 3], attributes: PointAttributes) {
+        match self {
+            OctreeNode::Cube(_) => {
             }
             OctreeNode::Sphere(sphere) => {
-                sphere.data_points.push(data_point);
+                sphere.add_data_point(data_point, Some(attributes));
             }
         }
     }
 
     pub fn is_inside(&self, point: &[f32; 3]) -> bool {
         match self {
-            OctreeNode::Cube(cube) => {
+            OctreeNode::Cube(_) => {
                 false
             }
             OctreeNode::Sphere(sphere) => {
@@ -164,12 +422,31 @@ impl OctreeNode {
         }
     }
 
+    // Whether the node stores `point` when it is routed to it. Loose spheres cover their whole
+    // cell, so points on the faces and corners of the cell are kept, even when rounding puts them
+    // just outside of it.
+    pub fn holds(&self, point: &[f32; 3], sphere_mode: SphereMode) -> bool {
+        let OctreeNode::Sphere(sphere) = self else {
+            return false;
+        };
+        let [min, max] = self.bounds();
+        let tolerance = (max[0] - min[0]) * 1e-5;
+        let in_cell = (0..3).all(|axis| min[axis] - tolerance <= point[axis] && point[axis] <= max[axis] + tolerance);
+        match sphere_mode {
+            SphereMode::Inscribed => sphere.is_inside(point),
+            SphereMode::Loose(LooseInsertion::All) => in_cell || sphere.is_inside(point),
+            SphereMode::Loose(LooseInsertion::BestFit) => in_cell,
+        }
+    }
+
+    // Leaves update their aggregates as points are added, inner nodes refresh theirs afterwards.
     pub fn import(&mut self, data_points: &[[f32; 3]]) {
         let nodes = self.nodes_mut();
         if let Some(nodes) = nodes {
             for node in nodes.iter_mut() {
                 node.import(data_points);
             }
+            self.refresh_aggregates();
         } else {
             for data_point in data_points {
                 if self.is_inside(data_point) {
@@ -179,6 +456,237 @@ impl OctreeNode {
         }
     }
 
+    // Routes every point to the children that can hold it, see `SphereMode`. Undivided nodes above
+    // `Layout::depth` are divided on the way down, with lazy division leaves holding more than
+    // the capacity are divided until the maximum depth. `attributes` is either empty or holds one
+    // entry per point.
+    pub fn import_routed(&mut self, data_points: &[[f32; 3]], attributes: &[PointAttributes], depth: usize, layout: &Layout) {
+        if let Some(nodes) = self.nodes_mut() {
+            let margin = layout.sphere_mode.margin();
+
+            #[cfg(feature = "rayon")]
+            {
+                use rayon::prelude::*;
+
+                let parts = route_parallel(nodes, data_points, attributes, margin);
+                nodes.par_iter_mut().zip(parts.par_iter()).for_each(|(node, (part, part_attributes))| {
+                    if !part.is_empty() {
+                        node.import_routed(part, part_attributes, depth + 1, layout);
+                    }
+                });
+            }
+
+            #[cfg(not(feature = "rayon"))]
+            {
+                let parts = route(nodes, data_points, attributes, margin);
+                for (node, (part, part_attributes)) in nodes.iter_mut().zip(parts) {
+                    if !part.is_empty() {
+                        node.import_routed(&part, &part_attributes, depth + 1, layout);
+                    }
+                }
+            }
+            self.refresh_aggregates();
+            return;
+        }
+
+        // Siblings added by `Octree::grow_to_fit` start out undivided, they get the levels down
+        // to `Layout::depth` once points reach them.
+        if depth < layout.depth {
+            let kind = layout.kind(depth + 1);
+            self.set_nodes(octree_divide_into_cube(&self.cube_points()).map(|cube| kind.node(cube, layout.sphere_mode)));
+            self.import_routed(data_points, attributes, depth, layout);
+            return;
+        }
+
+        for (i, data_point) in data_points.iter().enumerate() {
+            if self.holds(data_point, layout.sphere_mode) {
+                match attributes.get(i) {
+                    Some(attributes) => self.add_data_point_with_attributes(*data_point, *attributes),
+                    None => self.add_data_point(*data_point),
+                }
+            }
+        }
+
+        if let Division::Lazy { capacity, max_depth } = layout.division {
+            if self.data_points().len() > capacity && depth < max_depth {
+                let attributes = self.take_attributes();
+                let data_points = self.take_data_points();
+                let kind = layout.kind(depth + 1);
+                self.set_nodes(octree_divide_into_cube(&self.cube_points()).map(|cube| kind.node(cube, layout.sphere_mode)));
+                self.import_routed(&data_points, &attributes, depth, layout);
+            }
+        }
+    }
+
+    // Also clears the attributes of the points.
+    pub fn take_data_points(&mut self) -> Vec<[f32; 3]> {
+        match self {
+            OctreeNode::Cube(_) => {
+                Vec::new()
+            }
+            OctreeNode::Sphere(sphere) => {
+                sphere.attributes.clear();
+                *sphere.aggregates = Aggregates::default();
+                std::mem::take(&mut sphere.data_points)
+            }
+        }
+    }
+
+    pub fn take_attributes(&mut self) -> Vec<PointAttributes> {
+        match self {
+            OctreeNode::Cube(_) => {
+                Vec::new()
+            }
+            OctreeNode::Sphere(sphere) => {
+                std::mem::take(&mut sphere.attributes)
+            }
+        }
+    }
+
+    pub fn data_points(&self) -> &[[f32; 3]] {
+        match self {
+            OctreeNode::Cube(_) => {
+                &[]
+            }
+            OctreeNode::Sphere(sphere) => {
+                &sphere.data_points
+            }
+        }
+    }
+
+    pub fn attributes(&self) -> &[PointAttributes] {
+        match self {
+            OctreeNode::Cube(_) => {
+                &[]
+            }
+            OctreeNode::Sphere(sphere) => {
+                &sphere.attributes
+            }
+        }
+    }
+
+    pub fn aggregates(&self) -> &Aggregates {
+        match self {
+            OctreeNode::Cube(cube) => {
+                &cube.aggregates
+            }
+            OctreeNode::Sphere(sphere) => {
+                &sphere.aggregates
+            }
+        }
+    }
+
+    pub fn aggregates_mut(&mut self) -> &mut Aggregates {
+        match self {
+            OctreeNode::Cube(cube) => {
+                &mut cube.aggregates
+            }
+            OctreeNode::Sphere(sphere) => {
+                &mut sphere.aggregates
+            }
+        }
+    }
+
+    pub fn bounds(&self) -> [[f32; 3]; 2] {
+        let cube_points = self.cube_points();
+        [cube_points[0], cube_points[6]]
+    }
+
+    // Cell bounds grown by `margin` times the cell size, stored points never lie outside of it.
+    pub fn reach(&self, margin: f32) -> [[f32; 3]; 2] {
+        let [min, max] = self.bounds();
+        let grow = (max[0] - min[0]) * margin;
+        [min.map(|value| value - grow), max.map(|value| value + grow)]
+    }
+
+    // `best` holds the closest point found so far and its squared distance, `margin` is
+    // `SphereMode::margin` of the tree.
+    pub fn nearest(&self, point: &[f32; 3], margin: f32, best: &mut Option<([f32; 3], f32)>) {
+        if let Some((_, best_distance)) = best {
+            if bounds_distance_squared(&self.reach(margin), point) > *best_distance {
+                return;
+            }
+        }
+
+        for data_point in self.data_points() {
+            let distance = distance_squared(data_point, point);
+            if best.is_none_or(|(_, best_distance)| distance < best_distance) {
+                *best = Some((*data_point, distance));
+            }
+        }
+
+        if let Some(nodes) = self.nodes_ref() {
+            for i in children_by_distance(nodes, point) {
+                nodes[i].nearest(point, margin, best);
+            }
+        }
+    }
+
+    // `best` is kept sorted by squared distance and holds at most `k` entries.
+    pub fn k_nearest(&self, point: &[f32; 3], k: usize, margin: f32, best: &mut Vec<([f32; 3], f32)>) {
+        if best.len() == k && bounds_distance_squared(&self.reach(margin), point) > best[k - 1].1 {
+            return;
+        }
+
+        for data_point in self.data_points() {
+            let distance = distance_squared(data_point, point);
+            if best.len() < k || distance < best[k - 1].1 {
+                let i = best.partition_point(|(_, best_distance)| *best_distance <= distance);
+                best.insert(i, (*data_point, distance));
+                best.truncate(k);
+            }
+        }
+
+        if let Some(nodes) = self.nodes_ref() {
+            for i in children_by_distance(nodes, point) {
+                nodes[i].k_nearest(point, k, margin, best);
+            }
+        }
+    }
+
+    pub fn into_points(self, output: &mut Vec<[f32; 3]>) {
+        let (data_points, nodes) = match self {
+            OctreeNode::Cube(cube) => (Vec::new(), cube.nodes),
+            OctreeNode::Sphere(sphere) => (sphere.data_points, sphere.nodes),
+        };
+
+        output.extend_from_slice(&data_points);
+        drop(data_points);
+
+        if let Some(nodes) = nodes {
+            for node in *nodes {
+                node.into_points(output);
+            }
+        }
+    }
+
+    // `attributes` is left empty until a node with attributes is reached, from then on it gets
+    // one entry per point.
+    pub fn into_points_with_attributes(self, points: &mut Vec<[f32; 3]>, attributes: &mut Vec<PointAttributes>) {
+        let (data_points, data_attributes, nodes) = match self {
+            OctreeNode::Cube(cube) => (Vec::new(), Vec::new(), cube.nodes),
+            OctreeNode::Sphere(sphere) => {
+                let sphere = *sphere;
+                (sphere.data_points, sphere.attributes, sphere.nodes)
+            }
+        };
+
+        if !data_attributes.is_empty() || !attributes.is_empty() {
+            attributes.resize(points.len(), PointAttributes::default());
+            attributes.extend_from_slice(&data_attributes);
+            attributes.resize(points.len() + data_points.len(), PointAttributes::default());
+        }
+        points.extend_from_slice(&data_points);
+        drop(data_points);
+        drop(data_attributes);
+
+        if let Some(nodes) = nodes {
+            for node in *nodes {
+                node.into_points_with_attributes(points, attributes);
+            }
+        }
+    }
+
     pub fn export(&self, output: &mut Vec<[f32; 3]>) {
         match self {
             OctreeNode::Cube(cube) => {
@@ -189,7 +697,7 @@ impl OctreeNode {
                 }
             }
             OctreeNode::Sphere(sphere) => {
-                output.extend(sphere.data_points.clone());
+                output.extend_from_slice(&sphere.data_points);
                 if let Some(nodes) = sphere.nodes.as_ref() {
                     for node in nodes.iter() {
                         node.export(output);
@@ -200,6 +708,26 @@ impl OctreeNode {
     }
 }
 
+pub struct Leaves<'a> {
+    stack: Vec<&'a OctreeNode>,
+}
+
+impl<'a> Iterator for Leaves<'a> {
+    type Item = &'a [[f32; 3]];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(node) = self.stack.pop() {
+            if let Some(nodes) = node.nodes_ref() {
+                self.stack.extend(nodes.iter().rev());
+            }
+            if !node.data_points().is_empty() {
+                return Some(node.data_points());
+            }
+        }
+        None
+    }
+}
+
 impl OctreeSphere {
     pub fn divide(&mut self) {
         self.nodes = Some(Box::new(Self::into_cluster(self.cube_points)));
@@ -210,41 +738,56 @@ impl OctreeSphere {
         Self::from_cluster(cluster)
     }
 
-    pub fn from_cluster(points: [[[f32; 3]; 8]; 8]) -> [OctreeNode; 8] {
-        let size = (points[0][1][1] - points[0][0][1]).abs() / 3.0;
+    // Sphere touching the faces of its cell.
+    pub fn new(cube_points: [[f32; 3]; 8]) -> Self {
+        let size = (cube_points[1][1] - cube_points[0][1]).abs();
+        Self::with_radius(cube_points, size / 2.0)
+    }
 
-        let [middle0_x, middle0_y, middle0_z] = cube_middle(&points[0]);
-        let [middle1_x, middle1_y, middle1_z] = cube_middle(&points[1]);
-        let [middle2_x, middle2_y, middle2_z] = cube_middle(&points[2]);
-        let [middle3_x, middle3_y, middle3_z] = cube_middle(&points[3]);
+    // Sphere through the corners of its cell, so it overlaps the spheres of neighbouring cells.
+    pub fn loose(cube_points: [[f32; 3]; 8]) -> Self {
+        let size = (cube_points[1][1] - cube_points[0][1]).abs();
+        Self::with_radius(cube_points, size * 3f32.sqrt() / 2.0)
+    }
 
-        let [middle4_x, middle4_y, middle4_z] = cube_middle(&points[4]);
-        let [middle5_x, middle5_y, middle5_z] = cube_middle(&points[5]);
-        let [middle6_x, middle6_y, middle6_z] = cube_middle(&points[6]);
-        let [middle7_x, middle7_y, middle7_z] = cube_middle(&points[7]);
+    pub fn with_radius(cube_points: [[f32; 3]; 8], radius: f32) -> Self {
+        let [middle_x, middle_y, middle_z] = cube_middle(&cube_points);
 
-        [
-            OctreeNode::Sphere(OctreeSphere { cube_points: points[0], middle: [middle0_x, middle0_y, middle0_z], radius: size, sphere_points: gen_sphere(middle0_x, middle0_y, middle0_z, size), nodes: None, data_points: Vec::new() }),
-            OctreeNode::Sphere(OctreeSphere { cube_points: points[1], middle: [middle1_x, middle1_y, middle1_z], radius: size, sphere_points: gen_sphere(middle1_x, middle1_y, middle1_z, size), nodes: None, data_points: Vec::new() }),
-            OctreeNode::Sphere(OctreeSphere { cube_points: points[2], middle: [middle2_x, middle2_y, middle2_z], radius: size, sphere_points: gen_sphere(middle2_x, middle2_y, middle2_z, size), nodes: None, data_points: Vec::new() }),
-            OctreeNode::Sphere(OctreeSphere { cube_points: points[3], middle: [middle3_x, middle3_y, middle3_z], radius: size, sphere_points: gen_sphere(middle3_x, middle3_y, middle3_z, size), nodes: None, data_points: Vec::new() }),
-
-            OctreeNode::Sphere(OctreeSphere { cube_points: points[4], middle: [middle4_x, middle4_y, middle4_z], radius: size, sphere_points: gen_sphere(middle4_x, middle4_y, middle4_z, size), nodes: None, data_points: Vec::new() }),
-            OctreeNode::Sphere(OctreeSphere { cube_points: points[5], middle: [middle5_x, middle5_y, middle5_z], radius: size, sphere_points: gen_sphere(middle5_x, middle5_y, middle5_z, size), nodes: None, data_points: Vec::new() }),
-            OctreeNode::Sphere(OctreeSphere { cube_points: points[6], middle: [middle6_x, middle6_y, middle6_z], radius: size, sphere_points: gen_sphere(middle6_x, middle6_y, middle6_z, size), nodes: None, data_points: Vec::new() }),
-            OctreeNode::Sphere(OctreeSphere { cube_points: points[7], middle: [middle7_x, middle7_y, middle7_z], radius: size, sphere_points: gen_sphere(middle7_x, middle7_y, middle7_z, size), nodes: None, data_points: Vec::new() }),
-        ]
+        OctreeSphere {
+            cube_points,
+            middle: [middle_x, middle_y, middle_z],
+            radius,
+            sphere_points: gen_sphere(middle_x, middle_y, middle_z, radius),
+            nodes: None,
+            data_points: Vec::new(),
+            attributes: Vec::new(),
+            aggregates: Box::default(),
+        }
     }
 
-    pub fn is_inside(&self, point: &[f32; 3]) -> bool {
-        point[0] > self.middle[0] - self.radius && 
-        point[0] < self.middle[0] + self.radius &&
+    pub fn from_cluster(points: [[[f32; 3]; 8]; 8]) -> [OctreeNode; 8] {
+        points.map(|cube_points| OctreeNode::Sphere(Box::new(OctreeSphere::new(cube_points))))
+    }
 
-        point[1] > self.middle[1] - self.radius && 
-        point[1] < self.middle[1] + self.radius &&
+    pub fn is_inside(&self, point: &[f32; 3]) -> bool {
+        distance_squared(&self.middle, point) <= self.radius * self.radius
+    }
 
-        point[2] > self.middle[2] - self.radius && 
-        point[2] < self.middle[2] + self.radius
+    // Points added without attributes get default ones once any point of the sphere has some.
+    pub fn add_data_point(&mut self, data_point: [f32; 3], attributes: Option<PointAttributes>) {
+        match attributes {
+            Some(attributes) => {
+                self.attributes.resize(self.data_points.len(), PointAttributes::default());
+                self.attributes.push(attributes);
+            }
+            None => {
+                if !self.attributes.is_empty() {
+                    self.attributes.push(PointAttributes::default());
+                }
+            }
+        }
+        self.data_points.push(data_point);
+        self.aggregates.add(&data_point, attributes.as_ref());
     }
 }
 
@@ -260,19 +803,168 @@ impl OctreeCube {
 
     pub fn from_cluster(points: [[[f32; 3]; 8]; 8]) -> [OctreeNode; 8] {
         [
-            OctreeNode::Cube(OctreeCube { cube_points: points[0], nodes: None }),
-            OctreeNode::Cube(OctreeCube { cube_points: points[1], nodes: None }),
-            OctreeNode::Cube(OctreeCube { cube_points: points[2], nodes: None }),
-            OctreeNode::Cube(OctreeCube { cube_points: points[3], nodes: None }),
-
-            OctreeNode::Cube(OctreeCube { cube_points: points[4], nodes: None }),
-            OctreeNode::Cube(OctreeCube { cube_points: points[5], nodes: None }),
-            OctreeNode::Cube(OctreeCube { cube_points: points[6], nodes: None }),
-            OctreeNode::Cube(OctreeCube { cube_points: points[7], nodes: None }),
+            OctreeNode::Cube(OctreeCube { cube_points: points[0], nodes: None, aggregates: Box::default() }),
+            OctreeNode::Cube(OctreeCube { cube_points: points[1], nodes: None, aggregates: Box::default() }),
+            OctreeNode::Cube(OctreeCube { cube_points: points[2], nodes: None, aggregates: Box::default() }),
+            OctreeNode::Cube(OctreeCube { cube_points: points[3], nodes: None, aggregates: Box::default() }),
+
+            OctreeNode::Cube(OctreeCube { cube_points: points[4], nodes: None, aggregates: Box::default() }),
+            OctreeNode::Cube(OctreeCube { cube_points: points[5], nodes: None, aggregates: Box::default() }),
+            OctreeNode::Cube(OctreeCube { cube_points: points[6], nodes: None, aggregates: Box::default() }),
+            OctreeNode::Cube(OctreeCube { cube_points: points[7], nodes: None, aggregates: Box::default() }),
         ]
     }
 }
 
+pub fn points_bounds(data: &[[f32; 3]]) -> Option<[[f32; 3]; 2]> {
+    let first = *data.first()?;
+    let mut bounds = [first, first];
+    for data_point in data {
+        for axis in 0..3 {
+            bounds[0][axis] = bounds[0][axis].min(data_point[axis]);
+            bounds[1][axis] = bounds[1][axis].max(data_point[axis]);
+        }
+    }
+    Some(bounds)
+}
+
+type Part = (Vec<[f32; 3]>, Vec<PointAttributes>);
+
+// Splits the points between the children, in loose trees a point can go to several of them.
+fn route(nodes: &[OctreeNode; 8], data_points: &[[f32; 3]], attributes: &[PointAttributes], margin: f32) -> [Part; 8] {
+    let reaches = nodes.each_ref().map(|node| node.reach(margin));
+
+    let mut parts: [Part; 8] = Default::default();
+    let mut push = |i: usize, index: usize| {
+        parts[i].0.push(data_points[index]);
+        if let Some(attributes) = attributes.get(index) {
+            parts[i].1.push(*attributes);
+        }
+    };
+    for (index, data_point) in data_points.iter().enumerate() {
+        if margin > 0.0 {
+            for (i, [min, max]) in reaches.iter().enumerate() {
+                if (0..3).all(|axis| min[axis] <= data_point[axis] && data_point[axis] <= max[axis]) {
+                    push(i, index);
+                }
+            }
+        } else if let Some(i) = child_containing(nodes, data_point) {
+            push(i, index);
+        }
+    }
+    parts
+}
+
+// Routes chunks of the points on the thread pool and joins the parts in chunk order, so every
+// child gets its points in the same order as with `route`.
+#[cfg(feature = "rayon")]
+fn route_parallel(nodes: &[OctreeNode; 8], data_points: &[[f32; 3]], attributes: &[PointAttributes], margin: f32) -> [Part; 8] {
+    use rayon::prelude::*;
+
+    const CHUNK_SIZE: usize = 1 << 16;
+    if data_points.len() <= CHUNK_SIZE {
+        return route(nodes, data_points, attributes, margin);
+    }
+
+    let chunks: Vec<[Part; 8]> = data_points
+        .par_chunks(CHUNK_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let start = i * CHUNK_SIZE;
+            let chunk_attributes = attributes.get(start..start + chunk.len()).unwrap_or(&[]);
+            route(nodes, chunk, chunk_attributes, margin)
+        })
+        .collect();
+
+    let mut parts: [Part; 8] = std::array::from_fn(|i| {
+        let points = chunks.iter().map(|chunk| chunk[i].0.len()).sum();
+        let attributes = chunks.iter().map(|chunk| chunk[i].1.len()).sum();
+        (Vec::with_capacity(points), Vec::with_capacity(attributes))
+    });
+    for chunk in chunks {
+        for (part, (points, attributes)) in parts.iter_mut().zip(chunk) {
+            part.0.extend(points);
+            part.1.extend(attributes);
+        }
+    }
+    parts
+}
+
+// Cells are half open, except that children in the upper half of an axis also take the points on
+// the max face of their parent. Their own max is not compared, it can round below the parent's.
+fn child_containing(nodes: &[OctreeNode; 8], point: &[f32; 3]) -> Option<usize> {
+    let parent_min = nodes[0].bounds()[0];
+    nodes.iter().position(|node| {
+        let [min, max] = node.bounds();
+        (0..3).all(|axis| min[axis] <= point[axis] && (point[axis] < max[axis] || min[axis] > parent_min[axis]))
+    })
+}
+
+// Child whose cell holds `point`, or the closest one for points outside of the parent.
+fn child_towards(nodes: &[OctreeNode; 8], point: &[f32; 3]) -> usize {
+    // The child in the upper half of every axis starts at the middle of the parent.
+    let middle = nodes[6].bounds()[0];
+    nodes
+        .iter()
+        .position(|node| {
+            let [min, _] = node.bounds();
+            (0..3).all(|axis| (min[axis] >= middle[axis]) == (point[axis] >= middle[axis]))
+        })
+        .unwrap_or(0)
+}
+
+fn children_by_distance(nodes: &[OctreeNode; 8], point: &[f32; 3]) -> [usize; 8] {
+    let mut order = [0, 1, 2, 3, 4, 5, 6, 7];
+    let distances = nodes.each_ref().map(|node| bounds_distance_squared(&node.bounds(), point));
+    order.sort_by(|a, b| distances[*a].total_cmp(&distances[*b]));
+    order
+}
+
+pub fn distance_squared(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    let dx = a[0] - b[0];
+    let dy = a[1] - b[1];
+    let dz = a[2] - b[2];
+    dx * dx + dy * dy + dz * dz
+}
+
+pub fn bounds_distance_squared(bounds: &[[f32; 3]; 2], point: &[f32; 3]) -> f32 {
+    let [min, max] = bounds;
+    let mut distance = 0.0;
+    for axis in 0..3 {
+        let d = (min[axis] - point[axis]).max(point[axis] - max[axis]).max(0.0);
+        distance += d * d;
+    }
+    distance
+}
+
+// Normal of the best fitting plane through `points`, `None` for fewer than 3 points.
+pub fn estimate_normal(points: &[[f32; 3]]) -> Option<[f32; 3]> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    let count = points.len() as f64;
+    let centroid = points
+        .iter()
+        .map(|[x, y, z]| nalgebra::Vector3::new(*x as f64, *y as f64, *z as f64))
+        .sum::<nalgebra::Vector3<f64>>() / count;
+
+    let mut covariance = nalgebra::Matrix3::zeros();
+    for [x, y, z] in points {
+        let d = nalgebra::Vector3::new(*x as f64, *y as f64, *z as f64) - centroid;
+        covariance += d * d.transpose();
+    }
+
+    let eigen = covariance.symmetric_eigen();
+    let smallest = eigen.eigenvalues.imin();
+    let normal = eigen.eigenvectors.column(smallest);
+    if !normal.iter().all(|value| value.is_finite()) {
+        return None;
+    }
+
+    Some([normal.x as f32, normal.y as f32, normal.z as f32])
+}
+
 pub fn cube_middle(cube: &[[f32; 3]; 8]) -> [f32; 3] {
     let [start_x, start_y, start_z] = cube[0];
     let width_x = cube[3][0];
@@ -392,3 +1084,217 @@ pub fn octree_divide_into_cube(octree_cube: &[[f32; 3]; 8]) -> [[[f32; 3]; 8]; 8
 
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Spheres that keep every point of their cell, once.
+    fn loose(size: f32) -> OctreeBuilder {
+        OctreeBuilder::new(size).sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+    }
+
+    // Lattice over the whole cell, including its faces, edges and corners.
+    fn lattice(origin: [f32; 3], size: f32, steps: usize) -> Vec<[f32; 3]> {
+        let step = size / steps as f32;
+        let mut points = Vec::new();
+        for x in 0..=steps {
+            for y in 0..=steps {
+                for z in 0..=steps {
+                    points.push([origin[0] + x as f32 * step, origin[1] + y as f32 * step, origin[2] + z as f32 * step]);
+                }
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn import_keeps_every_point() {
+        let points = lattice([0.0, 0.0, 0.0], 10.0, 20);
+
+        let mut tree = loose(10.0).build().unwrap();
+        tree.import(&points);
+        assert_eq!(tree.point_count(), points.len());
+        assert!(tree.validate().is_empty());
+
+        // The default inscribed spheres drop the points in the cell corners.
+        let mut tree = Octree::new(10.0);
+        tree.import(&points);
+        assert!(tree.point_count() < points.len());
+    }
+
+    #[test]
+    fn import_keeps_every_point_off_origin() {
+        let origin = [-3.7, 120.25, 5000.1];
+        let points = lattice(origin, 7.3, 16);
+
+        let mut tree = loose(7.3)
+            .origin(origin)
+            .division(Division::Lazy { capacity: 50, max_depth: 8 })
+            .build()
+            .unwrap();
+        tree.import(&points);
+        assert_eq!(tree.point_count(), points.len());
+        assert!(tree.validate().is_empty());
+    }
+
+    #[test]
+    fn import_drops_points_outside_of_the_root() {
+        let mut tree = loose(10.0).build().unwrap();
+        tree.import(&[[-0.5, 5.0, 5.0], [5.0, 10.5, 5.0], [5.0, 5.0, 5.0]]);
+        assert_eq!(tree.point_count(), 1);
+    }
+
+    #[test]
+    fn import_with_attributes_rejects_missing_attributes() {
+        let mut tree = Octree::new(10.0);
+        let result = tree.import_with_attributes(&[[1.0, 1.0, 1.0], [2.0, 2.0, 2.0]], &[PointAttributes::default()]);
+        assert_eq!(result, Err(OctreeError::AttributeCountMismatch { points: 2, attributes: 1 }));
+        assert_eq!(tree.point_count(), 0);
+    }
+
+    #[test]
+    fn inscribed_spheres_drop_the_corners() {
+        let mut tree = OctreeBuilder::new(10.0).sphere_mode(SphereMode::Inscribed).build().unwrap();
+        tree.import(&[[0.0, 0.0, 0.0], [0.625, 0.625, 0.625]]);
+        assert_eq!(tree.point_count(), 1);
+    }
+
+    #[test]
+    fn try_new_rejects_invalid_sizes() {
+        for size in [0.0, -1.0, f32::INFINITY] {
+            assert_eq!(Octree::try_new(size).err(), Some(OctreeError::InvalidSize(size)));
+        }
+        assert!(matches!(Octree::try_new(f32::NAN), Err(OctreeError::InvalidSize(size)) if size.is_nan()));
+        assert!(Octree::try_new(10.0).is_ok());
+    }
+
+    #[test]
+    fn try_import_rejects_non_finite_points() {
+        let mut tree = Octree::new(10.0);
+        let result = tree.try_import(&[[1.0, 1.0, 1.0], [2.0, f32::NAN, 2.0], [f32::INFINITY, 0.0, 0.0]]);
+        assert!(matches!(result, Err(OctreeError::NonFinitePoint { index: 1, .. })), "{result:?}");
+        assert_eq!(tree.point_count(), 0);
+    }
+
+    #[test]
+    fn builder_rejects_invalid_layouts() {
+        assert_eq!(OctreeBuilder::new(10.0).depth(40).build().err(), Some(OctreeError::DepthTooLarge(40)));
+        let deep = Division::Lazy { capacity: 8, max_depth: 32 };
+        assert_eq!(OctreeBuilder::new(10.0).division(deep).build().err(), Some(OctreeError::DepthTooLarge(32)));
+        let empty = Division::Lazy { capacity: 0, max_depth: 6 };
+        assert_eq!(OctreeBuilder::new(10.0).division(empty).build().err(), Some(OctreeError::ZeroCapacity));
+    }
+
+    #[test]
+    fn try_node_reports_missing_children() {
+        let mut tree = Octree::new(10.0);
+        assert_eq!(tree.root.try_node(8).err(), Some(OctreeError::ChildIndexOutOfRange(8)));
+        assert!(tree.root.try_node(7).is_ok());
+
+        let key = NodeKey::new(tree.layout.depth as u8, 0, 0, 0);
+        let leaf = tree.get_mut(&key).unwrap();
+        assert_eq!(leaf.try_node(0).err(), Some(OctreeError::NotDivided));
+    }
+
+    #[test]
+    fn merge_grows_and_moves_the_old_root() {
+        let mut tree = loose(10.0).build().unwrap();
+        tree.import(&[[1.0, 1.0, 1.0], [9.0, 9.0, 9.0]]);
+        let old_bounds = tree.bounds();
+
+        let mut other = loose(10.0).origin([-10.0, 0.0, 0.0]).build().unwrap();
+        let attributes = PointAttributes { intensity: 7, ..PointAttributes::default() };
+        other.import_with_attributes(&[[-5.0, 5.0, 5.0]], &[attributes]).unwrap();
+
+        let old_root_key = tree.merge(other).unwrap();
+        assert_eq!(tree.point_count(), 3);
+        assert_ne!(old_root_key, NodeKey::ROOT);
+        assert_eq!(tree.get(&old_root_key).map(|node| node.bounds()), Some(old_bounds));
+        assert!(tree.export_attributes().iter().any(|attributes| attributes.intensity == 7));
+    }
+
+    #[test]
+    fn merge_grows_several_levels_without_dividing_empty_siblings() {
+        let mut tree = loose(10.0).build().unwrap();
+        tree.import(&[[1.0, 1.0, 1.0], [9.0, 9.0, 9.0]]);
+        let old_bounds = tree.bounds();
+        let old_depth = tree.layout.depth;
+
+        let mut other = loose(10.0).origin([995.0, 0.0, 0.0]).build().unwrap();
+        other.import(&[[1000.0, 5.0, 5.0]]);
+
+        let old_root_key = tree.merge(other).unwrap();
+        assert_eq!(old_root_key.depth, 7);
+        assert_eq!(tree.layout.depth, old_depth + 7);
+        assert_eq!(tree.get(&old_root_key).map(|node| node.bounds()), Some(old_bounds));
+        assert_eq!(tree.point_count(), 3);
+        assert_eq!(tree.leaf_key(&[1000.0, 5.0, 5.0]).depth as usize, tree.layout.depth);
+        assert!(tree.validate().is_empty());
+
+        // The old tree, the new roots with their empty siblings and one path down to the new point.
+        let mut node_count = 0;
+        tree.for_each_node(|_, _| node_count += 1);
+        assert_eq!(node_count, 585 + 7 * 8 + (tree.layout.depth - 1) * 8);
+    }
+
+    #[test]
+    fn merge_past_the_depth_limit_leaves_the_tree_as_it_is() {
+        let mut tree = loose(10.0)
+            .division(Division::Lazy { capacity: 4, max_depth: NodeKey::MAX_DEPTH as usize })
+            .build()
+            .unwrap();
+        tree.import(&[[1.0, 1.0, 1.0]]);
+        let old_bounds = tree.bounds();
+        let old_layout = tree.layout.clone();
+
+        let mut other = loose(10.0).origin([20.0, 0.0, 0.0]).build().unwrap();
+        other.import(&[[25.0, 5.0, 5.0]]);
+
+        assert_eq!(tree.merge(other), Err(OctreeError::DepthTooLarge(NodeKey::MAX_DEPTH as usize + 1)));
+        assert_eq!(tree.bounds(), old_bounds);
+        assert_eq!(tree.layout, old_layout);
+        assert_eq!(tree.point_count(), 1);
+    }
+
+    // Batches larger than a routing chunk are routed on the thread pool, single points never are.
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_import_matches_serial_import() {
+        let builder = loose(10.0).division(Division::Lazy { capacity: 64, max_depth: 8 });
+        let mut points = Vec::new();
+        let mut attributes = Vec::new();
+        for i in 0..42 {
+            for j in 0..42 {
+                for k in 0..42 {
+                    points.push([i as f32 * 0.23 + 0.05, j as f32 * 0.23 + 0.05, k as f32 * 0.23 + 0.05]);
+                    attributes.push(PointAttributes { intensity: (i * 42 + j) as u16, ..PointAttributes::default() });
+                }
+            }
+        }
+
+        let mut parallel = builder.clone().build().unwrap();
+        parallel.import_with_attributes(&points, &attributes).unwrap();
+
+        let mut serial = builder.build().unwrap();
+        for (point, attributes) in points.iter().zip(attributes.iter()) {
+            serial.import_with_attributes(&[*point], &[*attributes]).unwrap();
+        }
+
+        assert_eq!(parallel.point_count(), points.len());
+        assert_eq!(parallel.export(), serial.export());
+        assert_eq!(parallel.export_attributes(), serial.export_attributes());
+    }
+
+    #[test]
+    fn symmetric_diff_reports_both_sides() {
+        let mut tree = loose(10.0).build().unwrap();
+        tree.import(&[[1.0, 1.0, 1.0], [5.0, 5.0, 5.0]]);
+        let mut other = loose(10.0).build().unwrap();
+        other.import(&[[5.0, 5.0, 5.001], [8.0, 8.0, 8.0]]);
+
+        let (only_tree, only_other) = tree.symmetric_diff(&other, 0.01);
+        assert_eq!(only_tree, vec![[1.0, 1.0, 1.0]]);
+        assert_eq!(only_other, vec![[8.0, 8.0, 8.0]]);
+    }
+}
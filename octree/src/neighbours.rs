@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+use crate::{NodeKey, Octree, OctreeNode};
+
+// Ordered from the strongest to the weakest contact, so `Adjacency::Edge` as a limit also
+// includes face neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Adjacency {
+    Face,
+    Edge,
+    Vertex,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Neighbour {
+    pub key: NodeKey,
+    pub adjacency: Adjacency,
+}
+
+impl Octree {
+    // Leaves touching the node at `key` with at most `limit` contact. Leaves can be larger than
+    // the node when that side of the tree is divided less, or smaller when it is divided more.
+    pub fn neighbours(&self, key: &NodeKey, limit: Adjacency) -> Vec<Neighbour> {
+        let mut found = BTreeMap::new();
+        if self.get(key).is_none() {
+            return Vec::new();
+        }
+
+        let cells = 1i64 << key.depth;
+        for dx in -1i64..=1 {
+            for dy in -1i64..=1 {
+                for dz in -1i64..=1 {
+                    let offsets = [dx, dy, dz].iter().filter(|offset| **offset != 0).count();
+                    let adjacency = match offsets {
+                        1 => Adjacency::Face,
+                        2 => Adjacency::Edge,
+                        3 => Adjacency::Vertex,
+                        _ => continue,
+                    };
+                    if adjacency > limit {
+                        continue;
+                    }
+
+                    let [x, y, z] = [key.x as i64 + dx, key.y as i64 + dy, key.z as i64 + dz];
+                    if [x, y, z].iter().any(|value| *value < 0 || *value >= cells) {
+                        continue;
+                    }
+                    let target = NodeKey::new(key.depth, x as u32, y as u32, z as u32);
+
+                    let mut node = &self.root;
+                    let mut node_key = NodeKey::ROOT;
+                    for i in target.child_indices() {
+                        match node.child(i) {
+                            Some(child) => {
+                                node = child;
+                                node_key = node_key.child(i);
+                            }
+                            None => break,
+                        }
+                    }
+
+                    collect_touching(node_key, node, key, limit, &mut found);
+                }
+            }
+        }
+
+        found
+            .into_iter()
+            .map(|(key, adjacency)| Neighbour { key, adjacency })
+            .collect()
+    }
+}
+
+fn collect_touching(
+    node_key: NodeKey,
+    node: &OctreeNode,
+    key: &NodeKey,
+    limit: Adjacency,
+    found: &mut BTreeMap<NodeKey, Adjacency>,
+) {
+    let Some(adjacency) = adjacency(key, &node_key) else {
+        return;
+    };
+    if adjacency > limit {
+        return;
+    }
+
+    match node.nodes_ref() {
+        Some(nodes) => {
+            for (i, child) in nodes.iter().enumerate() {
+                collect_touching(node_key.child(i), child, key, limit, found);
+            }
+        }
+        None => {
+            found.insert(node_key, adjacency);
+        }
+    }
+}
+
+// How two cells touch, `None` when they are apart or overlap.
+pub fn adjacency(a: &NodeKey, b: &NodeKey) -> Option<Adjacency> {
+    let depth = a.depth.max(b.depth);
+    let range = |key: &NodeKey, value: u32| -> (u64, u64) {
+        let shift = depth - key.depth;
+        ((value as u64) << shift, (value as u64 + 1) << shift)
+    };
+
+    let mut touching_axes = 0;
+    for (a_value, b_value) in [(a.x, b.x), (a.y, b.y), (a.z, b.z)] {
+        let (a_start, a_end) = range(a, a_value);
+        let (b_start, b_end) = range(b, b_value);
+        if a_end < b_start || b_end < a_start {
+            return None;
+        }
+        if a_end == b_start || b_end == a_start {
+            touching_axes += 1;
+        }
+    }
+
+    match touching_axes {
+        1 => Some(Adjacency::Face),
+        2 => Some(Adjacency::Edge),
+        3 => Some(Adjacency::Vertex),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::Adjacency;
+    use crate::{Division, LooseInsertion, NodeKey, NodeKind, Octree, SphereMode};
+
+    // Leaves of size 4 except the one at the origin, which is divided into leaves of size 2.
+    fn uneven_tree() -> Octree {
+        let mut tree = Octree::builder(8.0)
+            .kinds(&[NodeKind::Cube, NodeKind::Sphere])
+            .depth(1)
+            .division(Division::Lazy { capacity: 1, max_depth: 2 })
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        tree.import(&[[1.0, 1.0, 1.0], [3.0, 3.0, 3.0]]);
+        tree
+    }
+
+    fn neighbours(tree: &Octree, key: NodeKey, limit: Adjacency) -> BTreeMap<NodeKey, Adjacency> {
+        tree.neighbours(&key, limit)
+            .into_iter()
+            .map(|neighbour| (neighbour.key, neighbour.adjacency))
+            .collect()
+    }
+
+    #[test]
+    fn faces_of_a_large_leaf_reach_smaller_ones() {
+        let tree = uneven_tree();
+        let found = neighbours(&tree, NodeKey::new(1, 1, 0, 0), Adjacency::Face);
+
+        let mut expected = BTreeMap::new();
+        for (y, z) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            expected.insert(NodeKey::new(2, 1, y, z), Adjacency::Face);
+        }
+        expected.insert(NodeKey::new(1, 1, 1, 0), Adjacency::Face);
+        expected.insert(NodeKey::new(1, 1, 0, 1), Adjacency::Face);
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn small_leaf_reaches_larger_ones_up_to_the_limit() {
+        let tree = uneven_tree();
+        let key = NodeKey::new(2, 1, 1, 1);
+
+        let faces = neighbours(&tree, key, Adjacency::Face);
+        assert_eq!(faces.len(), 6);
+        assert_eq!(faces.get(&NodeKey::new(1, 1, 0, 0)), Some(&Adjacency::Face));
+        assert_eq!(faces.get(&NodeKey::new(2, 0, 1, 1)), Some(&Adjacency::Face));
+        assert!(faces.values().all(|adjacency| *adjacency == Adjacency::Face));
+
+        let edges = neighbours(&tree, key, Adjacency::Edge);
+        assert_eq!(edges.len(), 12);
+        assert_eq!(edges.get(&NodeKey::new(1, 1, 1, 0)), Some(&Adjacency::Edge));
+        assert_eq!(edges.get(&NodeKey::new(2, 0, 0, 1)), Some(&Adjacency::Edge));
+        assert!(!edges.contains_key(&NodeKey::new(1, 1, 1, 1)));
+
+        let vertices = neighbours(&tree, key, Adjacency::Vertex);
+        assert_eq!(vertices.len(), 14);
+        assert_eq!(vertices.get(&NodeKey::new(1, 1, 1, 1)), Some(&Adjacency::Vertex));
+        assert_eq!(vertices.get(&NodeKey::new(2, 0, 0, 0)), Some(&Adjacency::Vertex));
+    }
+
+    #[test]
+    fn leaves_at_the_root_boundary_only_look_inside() {
+        let tree = uneven_tree();
+
+        let corner = neighbours(&tree, NodeKey::new(2, 0, 0, 0), Adjacency::Vertex);
+        assert_eq!(corner.len(), 7);
+        assert!(corner.keys().all(|key| key.depth == 2));
+
+        let far_corner = neighbours(&tree, NodeKey::new(1, 1, 1, 1), Adjacency::Vertex);
+        assert_eq!(far_corner.len(), 7);
+        assert_eq!(far_corner.get(&NodeKey::new(2, 1, 1, 1)), Some(&Adjacency::Vertex));
+
+        assert!(tree.neighbours(&NodeKey::ROOT, Adjacency::Vertex).is_empty());
+    }
+}
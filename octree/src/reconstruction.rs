@@ -0,0 +1,436 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::{self, Write},
+};
+
+use crate::{distance_squared, estimate_normal, points_bounds, Octree, OctreeError};
+
+#[derive(Debug, Clone, Default)]
+pub struct TriangleMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReconstructionParams {
+    pub cell_size: f32,
+    // Neighbours used to fit the local plane at every grid vertex.
+    pub neighbours: usize,
+    // Grid vertices further than this from any point are left empty, which keeps the mesh open
+    // where there is no data. Defaults to twice the cell size when `None`.
+    pub max_distance: Option<f32>,
+    // Normals of the points are flipped to agree with their neighbours, starting from the point
+    // furthest along this direction whose normal points along it, once per connected part.
+    pub up: [f32; 3],
+}
+
+impl Default for ReconstructionParams {
+    fn default() -> Self {
+        Self {
+            cell_size: 1.0,
+            neighbours: 16,
+            max_distance: None,
+            up: [0.0, 1.0, 0.0],
+        }
+    }
+}
+
+type GridVertex = [i32; 3];
+
+// Corners are numbered by bits, x = 1, y = 2, z = 4. All tetrahedra share the 0-7 diagonal so
+// neighbouring cubes are split the same way along their common faces.
+const TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 1, 5, 7],
+    [0, 2, 3, 7],
+    [0, 2, 6, 7],
+    [0, 4, 5, 7],
+    [0, 4, 6, 7],
+];
+
+// Extracts the zero level of a signed distance field sampled on a sparse grid around the points.
+// The field is the distance to the plane fitted through the nearest points of each grid vertex,
+// and every grid cube is split into six tetrahedra before contouring. Fails when the cell size is
+// not finite and positive, or so small that grid indices overflow.
+pub fn reconstruct(tree: &Octree, params: &ReconstructionParams) -> Result<TriangleMesh, OctreeError> {
+    let cell_size = params.cell_size;
+    if !cell_size.is_finite() || cell_size <= 0.0 {
+        return Err(OctreeError::InvalidCellSize(cell_size));
+    }
+    let points: Vec<[f32; 3]> = tree.points().copied().collect();
+    if let Some([min, max]) = points_bounds(&points) {
+        let extent = min.iter().chain(max.iter()).fold(0.0f32, |extent, value| extent.max(value.abs()));
+        if extent / cell_size >= (i32::MAX / 2) as f32 {
+            return Err(OctreeError::InvalidCellSize(cell_size));
+        }
+    }
+    let max_distance = params.max_distance.unwrap_or(cell_size * 2.0);
+    let normals = oriented_normals(tree, &points, params);
+
+    let mut cells = HashSet::new();
+    for point in points.iter() {
+        let [x, y, z] = grid_cell(point, cell_size);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    cells.insert([x + dx, y + dy, z + dz]);
+                }
+            }
+        }
+    }
+
+    let mut field: HashMap<GridVertex, Option<f32>> = HashMap::new();
+    let mut sample = |vertex: GridVertex| -> Option<f32> {
+        *field.entry(vertex).or_insert_with(|| {
+            let position = grid_position(&vertex, cell_size);
+            signed_distance(tree, &normals, &position, max_distance, params)
+        })
+    };
+
+    let mut mesh = TriangleMesh::default();
+    let mut edge_vertices: HashMap<(GridVertex, GridVertex), u32> = HashMap::new();
+
+    let mut cells: Vec<GridVertex> = cells.into_iter().collect();
+    cells.sort_unstable();
+
+    for [x, y, z] in cells {
+        let corners: [GridVertex; 8] = std::array::from_fn(|i| {
+            [x + (i & 1) as i32, y + ((i >> 1) & 1) as i32, z + ((i >> 2) & 1) as i32]
+        });
+
+        let mut values = [0.0; 8];
+        let mut complete = true;
+        for (i, corner) in corners.iter().enumerate() {
+            match sample(*corner) {
+                Some(value) => values[i] = value,
+                None => {
+                    complete = false;
+                    break;
+                }
+            }
+        }
+        if !complete {
+            continue;
+        }
+
+        for tetrahedron in TETRAHEDRA {
+            let tetrahedron_corners = tetrahedron.map(|i| corners[i]);
+            let tetrahedron_values = tetrahedron.map(|i| values[i]);
+            polygonise(&tetrahedron_corners, &tetrahedron_values, cell_size, &mut mesh, &mut edge_vertices);
+        }
+    }
+
+    mesh.compute_normals();
+    Ok(mesh)
+}
+
+impl TriangleMesh {
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![[0.0f32; 3]; self.positions.len()];
+
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| self.positions[i as usize]);
+            let face_normal = cross(&sub(&b, &a), &sub(&c, &a));
+            for i in triangle {
+                for axis in 0..3 {
+                    normals[*i as usize][axis] += face_normal[axis];
+                }
+            }
+        }
+
+        for normal in normals.iter_mut() {
+            let length = distance_squared(normal, &[0.0, 0.0, 0.0]).sqrt();
+            if length > 0.0 {
+                *normal = normal.map(|value| value / length);
+            }
+        }
+
+        self.normals = normals;
+    }
+
+    pub fn write_obj(&self, mut writer: impl Write) -> io::Result<()> {
+        for [x, y, z] in self.positions.iter() {
+            writeln!(writer, "v {x} {y} {z}")?;
+        }
+        for [x, y, z] in self.normals.iter() {
+            writeln!(writer, "vn {x} {y} {z}")?;
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+            writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?;
+        }
+        Ok(())
+    }
+
+    pub fn write_ply(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format ascii 1.0")?;
+        writeln!(writer, "element vertex {}", self.positions.len())?;
+        writeln!(writer, "property float x")?;
+        writeln!(writer, "property float y")?;
+        writeln!(writer, "property float z")?;
+        writeln!(writer, "property float nx")?;
+        writeln!(writer, "property float ny")?;
+        writeln!(writer, "property float nz")?;
+        writeln!(writer, "element face {}", self.indices.len() / 3)?;
+        writeln!(writer, "property list uchar int vertex_indices")?;
+        writeln!(writer, "end_header")?;
+
+        for (i, [x, y, z]) in self.positions.iter().enumerate() {
+            let [nx, ny, nz] = self.normals.get(i).copied().unwrap_or([0.0, 0.0, 0.0]);
+            writeln!(writer, "{x} {y} {z} {nx} {ny} {nz}")?;
+        }
+        for triangle in self.indices.chunks_exact(3) {
+            writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?;
+        }
+        Ok(())
+    }
+}
+
+// Normals of the points keyed by their bits, flipped so neighbours agree. The orientation spreads
+// breadth first over the nearest neighbours of every point, which keeps it consistent across
+// smooth surfaces where a fixed direction would flip it, under a sphere for example.
+fn oriented_normals(tree: &Octree, points: &[[f32; 3]], params: &ReconstructionParams) -> HashMap<[u32; 3], [f32; 3]> {
+    let k = params.neighbours.max(3);
+    let mut indices = HashMap::new();
+    for point in points.iter() {
+        let next = indices.len();
+        indices.entry(point.map(f32::to_bits)).or_insert(next);
+    }
+    let mut unique = vec![[0.0; 3]; indices.len()];
+    for point in points.iter() {
+        unique[indices[&point.map(f32::to_bits)]] = *point;
+    }
+
+    let mut normals: Vec<Option<[f32; 3]>> = Vec::with_capacity(unique.len());
+    let mut neighbours: Vec<Vec<usize>> = Vec::with_capacity(unique.len());
+    for point in unique.iter() {
+        let nearest = tree.k_nearest(point, k);
+        normals.push(estimate_normal(&nearest));
+        neighbours.push(nearest.iter().filter_map(|neighbour| indices.get(&neighbour.map(f32::to_bits)).copied()).collect());
+    }
+
+    let mut seeds: Vec<usize> = (0..unique.len()).collect();
+    seeds.sort_by(|a, b| dot(&unique[*b], &params.up).total_cmp(&dot(&unique[*a], &params.up)));
+
+    let mut visited = vec![false; unique.len()];
+    let mut queue = VecDeque::new();
+    for seed in seeds {
+        let Some(normal) = normals[seed] else {
+            continue;
+        };
+        if visited[seed] {
+            continue;
+        }
+        if dot(&normal, &params.up) < 0.0 {
+            normals[seed] = Some(normal.map(|value| -value));
+        }
+        visited[seed] = true;
+        queue.push_back(seed);
+
+        while let Some(i) = queue.pop_front() {
+            let normal = normals[i].unwrap();
+            for j in neighbours[i].iter().copied() {
+                let Some(neighbour_normal) = normals[j] else {
+                    continue;
+                };
+                if visited[j] {
+                    continue;
+                }
+                if dot(&normal, &neighbour_normal) < 0.0 {
+                    normals[j] = Some(neighbour_normal.map(|value| -value));
+                }
+                visited[j] = true;
+                queue.push_back(j);
+            }
+        }
+    }
+
+    indices
+        .into_iter()
+        .filter_map(|(bits, i)| normals[i].map(|normal| (bits, normal)))
+        .collect()
+}
+
+fn signed_distance(
+    tree: &Octree,
+    normals: &HashMap<[u32; 3], [f32; 3]>,
+    position: &[f32; 3],
+    max_distance: f32,
+    params: &ReconstructionParams,
+) -> Option<f32> {
+    let neighbours = tree.k_nearest(position, params.neighbours.max(3));
+    let nearest = neighbours.first()?;
+    if distance_squared(nearest, position) > max_distance * max_distance {
+        return None;
+    }
+
+    // The local plane faces the same way as the oriented normals of the points it is fitted to.
+    let mut reference = [0.0; 3];
+    for neighbour in neighbours.iter() {
+        if let Some(normal) = normals.get(&neighbour.map(f32::to_bits)) {
+            for axis in 0..3 {
+                reference[axis] += normal[axis];
+            }
+        }
+    }
+    if reference == [0.0; 3] {
+        reference = params.up;
+    }
+
+    let mut normal = estimate_normal(&neighbours)?;
+    if dot(&normal, &reference) < 0.0 {
+        normal = normal.map(|value| -value);
+    }
+
+    let count = neighbours.len() as f32;
+    let mut centroid = [0.0; 3];
+    for neighbour in neighbours.iter() {
+        for axis in 0..3 {
+            centroid[axis] += neighbour[axis] / count;
+        }
+    }
+
+    Some(dot(&sub(position, &centroid), &normal))
+}
+
+fn polygonise(
+    corners: &[GridVertex; 4],
+    values: &[f32; 4],
+    cell_size: f32,
+    mesh: &mut TriangleMesh,
+    edge_vertices: &mut HashMap<(GridVertex, GridVertex), u32>,
+) {
+    let inside: Vec<usize> = (0..4).filter(|i| values[*i] < 0.0).collect();
+    let outside: Vec<usize> = (0..4).filter(|i| values[*i] >= 0.0).collect();
+
+    let mut edge_vertex = |a: usize, b: usize| -> u32 {
+        let key = if corners[a] < corners[b] { (corners[a], corners[b]) } else { (corners[b], corners[a]) };
+        *edge_vertices.entry(key).or_insert_with(|| {
+            let start = grid_position(&corners[a], cell_size);
+            let end = grid_position(&corners[b], cell_size);
+            let t = values[a] / (values[a] - values[b]);
+            mesh.positions.push(std::array::from_fn(|axis| start[axis] + (end[axis] - start[axis]) * t));
+            (mesh.positions.len() - 1) as u32
+        })
+    };
+
+    let triangles: Vec<[u32; 3]> = match (inside.as_slice(), outside.as_slice()) {
+        ([a], [b, c, d]) | ([b, c, d], [a]) => {
+            vec![[edge_vertex(*a, *b), edge_vertex(*a, *c), edge_vertex(*a, *d)]]
+        }
+        ([a, b], [c, d]) => {
+            let ac = edge_vertex(*a, *c);
+            let ad = edge_vertex(*a, *d);
+            let bc = edge_vertex(*b, *c);
+            let bd = edge_vertex(*b, *d);
+            vec![[ac, ad, bd], [ac, bd, bc]]
+        }
+        _ => Vec::new(),
+    };
+
+    // Wind every triangle so its normal points from the inside corners to the outside ones.
+    let inside_middle = middle(inside.iter().map(|i| grid_position(&corners[*i], cell_size)));
+    let outside_middle = middle(outside.iter().map(|i| grid_position(&corners[*i], cell_size)));
+    let direction = sub(&outside_middle, &inside_middle);
+
+    for [a, b, c] in triangles {
+        let [pa, pb, pc] = [a, b, c].map(|i| mesh.positions[i as usize]);
+        let normal = cross(&sub(&pb, &pa), &sub(&pc, &pa));
+        if dot(&normal, &direction) < 0.0 {
+            mesh.indices.extend([a, c, b]);
+        } else {
+            mesh.indices.extend([a, b, c]);
+        }
+    }
+}
+
+fn grid_cell(point: &[f32; 3], cell_size: f32) -> GridVertex {
+    point.map(|value| (value / cell_size).floor() as i32)
+}
+
+fn grid_position(vertex: &GridVertex, cell_size: f32) -> [f32; 3] {
+    vertex.map(|value| value as f32 * cell_size)
+}
+
+fn middle(points: impl Iterator<Item = [f32; 3]>) -> [f32; 3] {
+    let mut sum = [0.0; 3];
+    let mut count = 0.0;
+    for point in points {
+        for axis in 0..3 {
+            sum[axis] += point[axis];
+        }
+        count += 1.0;
+    }
+    sum.map(|value| value / count)
+}
+
+fn sub(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &[f32; 3], b: &[f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reconstruct, ReconstructionParams};
+    use crate::{distance_squared, LooseInsertion, Octree, OctreeError, SphereMode};
+
+    // Evenly spread points on a sphere of radius 5 around the origin.
+    fn sphere() -> Vec<[f32; 3]> {
+        let count = 3000;
+        let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+        (0..count)
+            .map(|i| {
+                let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+                let ring = (1.0 - y * y).sqrt();
+                let angle = golden_angle * i as f32;
+                [5.0 * ring * angle.cos(), 5.0 * y, 5.0 * ring * angle.sin()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn vertices_lie_on_a_sphere_with_outward_normals() {
+        let mut tree = Octree::builder(20.0)
+            .origin([-10.0, -10.0, -10.0])
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        tree.import(&sphere());
+
+        let mesh = reconstruct(&tree, &ReconstructionParams::default()).unwrap();
+        assert!(mesh.indices.len() > 300);
+        for (position, normal) in mesh.positions.iter().zip(mesh.normals.iter()) {
+            let radius = distance_squared(position, &[0.0, 0.0, 0.0]).sqrt();
+            assert!((radius - 5.0).abs() < 0.1, "{position:?} is {radius} from the middle");
+            let outward = position.map(|value| value / radius);
+            let alignment: f32 = (0..3).map(|axis| normal[axis] * outward[axis]).sum();
+            assert!(alignment > 0.9, "{normal:?} at {position:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_cell_sizes() {
+        let mut tree = Octree::builder(10.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        tree.import(&[[5.0, 5.0, 5.0]]);
+        for cell_size in [0.0, -1.0, f32::NAN, f32::INFINITY, 1e-9] {
+            let params = ReconstructionParams { cell_size, ..ReconstructionParams::default() };
+            assert!(matches!(reconstruct(&tree, &params), Err(OctreeError::InvalidCellSize(_))), "{cell_size}");
+        }
+    }
+}
@@ -0,0 +1,533 @@
+use nalgebra::{Matrix3, Matrix4, Vector3, Vector4};
+
+use crate::{estimate_normal, OctreeError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Primitive {
+    // Points satisfying `normal . p + d = 0`, `normal` has unit length.
+    Plane { normal: [f32; 3], d: f32 },
+    Sphere { middle: [f32; 3], radius: f32 },
+    // Infinite cylinder around the line through `point` along the unit `axis`.
+    Cylinder { point: [f32; 3], axis: [f32; 3], radius: f32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub primitive: Primitive,
+    pub inliers: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RansacParams {
+    pub distance_threshold: f32,
+    // Maximum angle in radians between a point normal and the surface, checked when normals are given.
+    pub angle_threshold: f32,
+    // Spheres and cylinders larger than this are rejected, they would otherwise swallow planes.
+    pub max_radius: f32,
+    // Most samples drawn per primitive, fewer when the best candidate so far makes it likely
+    // enough that no better one is left, see `confidence`.
+    pub iterations: usize,
+    // Probability of drawing at least one sample of inliers only, given the inlier ratio of the
+    // best candidate so far.
+    pub confidence: f32,
+    pub min_inliers: usize,
+    pub max_primitives: usize,
+    pub spheres: bool,
+    // Cylinders are only searched for when normals are passed to `segment`.
+    pub cylinders: bool,
+    pub seed: u64,
+}
+
+impl Default for RansacParams {
+    fn default() -> Self {
+        Self {
+            distance_threshold: 0.05,
+            angle_threshold: 0.35,
+            max_radius: 10.0,
+            iterations: 1000,
+            confidence: 0.99,
+            min_inliers: 100,
+            max_primitives: 16,
+            spheres: false,
+            cylinders: false,
+            seed: 0x5eed,
+        }
+    }
+}
+
+impl Primitive {
+    pub fn distance(&self, point: &[f32; 3]) -> f32 {
+        let p = vector(point);
+        match self {
+            Primitive::Plane { normal, d } => {
+                (vector(normal).dot(&p) + *d as f64).abs() as f32
+            }
+            Primitive::Sphere { middle, radius } => {
+                ((p - vector(middle)).norm() - *radius as f64).abs() as f32
+            }
+            Primitive::Cylinder { point, axis, radius } => {
+                let offset = p - vector(point);
+                let axis = vector(axis);
+                ((offset - axis * offset.dot(&axis)).norm() - *radius as f64).abs() as f32
+            }
+        }
+    }
+
+    pub fn normal(&self, point: &[f32; 3]) -> [f32; 3] {
+        let p = vector(point);
+        let normal = match self {
+            Primitive::Plane { normal, .. } => {
+                vector(normal)
+            }
+            Primitive::Sphere { middle, .. } => {
+                p - vector(middle)
+            }
+            Primitive::Cylinder { point, axis, .. } => {
+                let offset = p - vector(point);
+                let axis = vector(axis);
+                offset - axis * offset.dot(&axis)
+            }
+        };
+        array(&normal.try_normalize(1e-12).unwrap_or_else(Vector3::zeros))
+    }
+
+    fn radius(&self) -> f32 {
+        match self {
+            Primitive::Plane { .. } => 0.0,
+            Primitive::Sphere { radius, .. } | Primitive::Cylinder { radius, .. } => *radius,
+        }
+    }
+}
+
+// Repeatedly extracts the primitive with the most inliers until `params` stop it.
+// Inlier indices refer to `points`, `normals` need one entry per point.
+pub fn segment(points: &[[f32; 3]], normals: Option<&[[f32; 3]]>, params: &RansacParams) -> Result<Vec<Segment>, OctreeError> {
+    if let Some(normals) = normals {
+        if normals.len() != points.len() {
+            return Err(OctreeError::NormalCountMismatch { points: points.len(), normals: normals.len() });
+        }
+    }
+
+    // Early stops are sized for the largest sample drawn, so every kind of primitive gets its chance.
+    let sample_size = if params.spheres { 4 } else { 3 };
+    let mut rng = Rng(params.seed.max(1));
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut segments = Vec::new();
+
+    while segments.len() < params.max_primitives && remaining.len() >= params.min_inliers.max(4) {
+        let mut best: Option<(Primitive, usize)> = None;
+        let mut iterations = params.iterations;
+
+        let mut iteration = 0;
+        while iteration < iterations {
+            iteration += 1;
+            let mut candidates = Vec::with_capacity(3);
+
+            let [a, b, c] = rng.sample(&remaining);
+            if let Some(plane) = plane_from_points(&points[a], &points[b], &points[c]) {
+                candidates.push(plane);
+            }
+
+            if params.spheres {
+                let [a, b, c, d] = rng.sample(&remaining);
+                if let Some(sphere) = fit_sphere(&[points[a], points[b], points[c], points[d]]) {
+                    candidates.push(sphere);
+                }
+            }
+
+            if let (true, Some(normals)) = (params.cylinders, normals) {
+                let [a, b] = rng.sample(&remaining);
+                if let Some(cylinder) = cylinder_from_points(&points[a], &normals[a], &points[b], &normals[b]) {
+                    candidates.push(cylinder);
+                }
+            }
+
+            for candidate in candidates {
+                if candidate.radius() > params.max_radius {
+                    continue;
+                }
+
+                let count = remaining
+                    .iter()
+                    .filter(|i| is_inlier(&candidate, points, normals, **i, params))
+                    .count();
+                if best.is_none_or(|(_, best_count)| count > best_count) {
+                    best = Some((candidate, count));
+                    let ratio = count as f64 / remaining.len() as f64;
+                    iterations = iterations.min(needed_iterations(ratio, sample_size, params.confidence));
+                }
+            }
+        }
+
+        let Some((primitive, count)) = best else {
+            break;
+        };
+        if count < params.min_inliers {
+            break;
+        }
+
+        let inliers = |primitive: &Primitive| -> Vec<usize> {
+            remaining
+                .iter()
+                .copied()
+                .filter(|i| is_inlier(primitive, points, normals, *i, params))
+                .collect()
+        };
+
+        let mut primitive = primitive;
+        let mut segment_inliers = inliers(&primitive);
+        let inlier_points: Vec<[f32; 3]> = segment_inliers.iter().map(|i| points[*i]).collect();
+        let inlier_normals: Option<Vec<[f32; 3]>> = normals.map(|normals| segment_inliers.iter().map(|i| normals[*i]).collect());
+        if let Some(refined) = refine(&primitive, &inlier_points, inlier_normals.as_deref()) {
+            let refined_inliers = inliers(&refined);
+            if refined_inliers.len() >= segment_inliers.len() {
+                primitive = refined;
+                segment_inliers = refined_inliers;
+            }
+        }
+
+        let mut taken = vec![false; points.len()];
+        for i in segment_inliers.iter() {
+            taken[*i] = true;
+        }
+        remaining.retain(|i| !taken[*i]);
+
+        segments.push(Segment { primitive, inliers: segment_inliers });
+    }
+
+    Ok(segments)
+}
+
+// Samples needed to draw `sample_size` inliers at once with probability `confidence`, when a
+// share `ratio` of the points are inliers.
+fn needed_iterations(ratio: f64, sample_size: i32, confidence: f32) -> usize {
+    let all_inliers = ratio.powi(sample_size);
+    if all_inliers >= 1.0 {
+        return 1;
+    }
+    let iterations = (1.0 - confidence as f64).ln() / (1.0 - all_inliers).ln();
+    if iterations.is_finite() {
+        iterations.ceil().max(1.0) as usize
+    } else {
+        usize::MAX
+    }
+}
+
+fn is_inlier(primitive: &Primitive, points: &[[f32; 3]], normals: Option<&[[f32; 3]]>, i: usize, params: &RansacParams) -> bool {
+    if primitive.distance(&points[i]) > params.distance_threshold {
+        return false;
+    }
+
+    let Some(normals) = normals else {
+        return true;
+    };
+    let normal = vector(&normals[i]);
+    if normal.norm_squared() == 0.0 {
+        return true;
+    }
+
+    let surface_normal = vector(&primitive.normal(&points[i]));
+    let cos = normal.normalize().dot(&surface_normal).abs();
+    cos >= (params.angle_threshold as f64).cos()
+}
+
+// Least squares sphere through at least 4 points.
+pub fn fit_sphere(points: &[[f32; 3]]) -> Option<Primitive> {
+    if points.len() < 4 {
+        return None;
+    }
+
+    let centroid = points.iter().map(vector).sum::<Vector3<f64>>() / points.len() as f64;
+
+    // Solves x^2 + y^2 + z^2 + a x + b y + c z + d = 0 around the centroid.
+    let mut ata = Matrix4::zeros();
+    let mut atb = Vector4::zeros();
+    for point in points {
+        let p = vector(point) - centroid;
+        let row = Vector4::new(p.x, p.y, p.z, 1.0);
+        ata += row * row.transpose();
+        atb -= row * p.norm_squared();
+    }
+
+    let x = ata.lu().solve(&atb)?;
+    let middle = Vector3::new(-x[0] / 2.0, -x[1] / 2.0, -x[2] / 2.0);
+    let radius_squared = middle.norm_squared() - x[3];
+    if !radius_squared.is_finite() || radius_squared <= 0.0 {
+        return None;
+    }
+
+    Some(Primitive::Sphere {
+        middle: array(&(middle + centroid)),
+        radius: radius_squared.sqrt() as f32,
+    })
+}
+
+pub fn fit_plane(points: &[[f32; 3]]) -> Option<Primitive> {
+    let normal = vector(&estimate_normal(points)?).normalize();
+    let centroid = points.iter().map(vector).sum::<Vector3<f64>>() / points.len() as f64;
+
+    Some(Primitive::Plane {
+        normal: array(&normal),
+        d: -normal.dot(&centroid) as f32,
+    })
+}
+
+fn refine(primitive: &Primitive, inliers: &[[f32; 3]], normals: Option<&[[f32; 3]]>) -> Option<Primitive> {
+    match primitive {
+        Primitive::Plane { .. } => fit_plane(inliers),
+        Primitive::Sphere { .. } => fit_sphere(inliers),
+        Primitive::Cylinder { axis, .. } => {
+            let axis = match normals {
+                Some(normals) => fit_cylinder_axis(normals).unwrap_or(vector(axis)),
+                None => vector(axis),
+            };
+            fit_cylinder(inliers, &axis)
+        }
+    }
+}
+
+// The axis is the direction most perpendicular to every surface normal.
+fn fit_cylinder_axis(normals: &[[f32; 3]]) -> Option<Vector3<f64>> {
+    let mut scatter = Matrix3::zeros();
+    for normal in normals {
+        let normal = vector(normal);
+        scatter += normal * normal.transpose();
+    }
+
+    let eigen = scatter.symmetric_eigen();
+    let axis = eigen.eigenvectors.column(eigen.eigenvalues.imin()).into_owned();
+    axis.try_normalize(1e-12)
+}
+
+// Least squares circle of the points projected along `axis`.
+fn fit_cylinder(points: &[[f32; 3]], axis: &Vector3<f64>) -> Option<Primitive> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    let helper = if axis.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+    let u = axis.cross(&helper).normalize();
+    let v = axis.cross(&u);
+
+    let centroid = points.iter().map(vector).sum::<Vector3<f64>>() / points.len() as f64;
+
+    // Solves x^2 + y^2 + a x + b y + c = 0 in the (u, v) plane.
+    let mut ata = Matrix3::zeros();
+    let mut atb = Vector3::zeros();
+    for point in points {
+        let p = vector(point) - centroid;
+        let (x, y) = (p.dot(&u), p.dot(&v));
+        let row = Vector3::new(x, y, 1.0);
+        ata += row * row.transpose();
+        atb -= row * (x * x + y * y);
+    }
+
+    let solution = ata.lu().solve(&atb)?;
+    let (x, y) = (-solution[0] / 2.0, -solution[1] / 2.0);
+    let radius_squared = x * x + y * y - solution[2];
+    if !radius_squared.is_finite() || radius_squared <= 0.0 {
+        return None;
+    }
+
+    Some(Primitive::Cylinder {
+        point: array(&(centroid + u * x + v * y)),
+        axis: array(axis),
+        radius: radius_squared.sqrt() as f32,
+    })
+}
+
+fn plane_from_points(a: &[f32; 3], b: &[f32; 3], c: &[f32; 3]) -> Option<Primitive> {
+    let a = vector(a);
+    let normal = (vector(b) - a).cross(&(vector(c) - a));
+    let length = normal.norm();
+    if length < 1e-12 {
+        return None;
+    }
+    let normal = normal / length;
+
+    Some(Primitive::Plane {
+        normal: array(&normal),
+        d: -normal.dot(&a) as f32,
+    })
+}
+
+fn cylinder_from_points(a: &[f32; 3], normal_a: &[f32; 3], b: &[f32; 3], normal_b: &[f32; 3]) -> Option<Primitive> {
+    let (a, normal_a, b, normal_b) = (vector(a), vector(normal_a), vector(b), vector(normal_b));
+    let axis = normal_a.cross(&normal_b);
+    if axis.norm() < 1e-6 {
+        return None;
+    }
+    let axis = axis.normalize();
+
+    // Closest points between the two normal lines, their midpoint lies on the axis.
+    let w = a - b;
+    let ab = normal_a.dot(&normal_b);
+    let denominator = 1.0 - ab * ab;
+    let t = (ab * normal_b.dot(&w) - normal_a.dot(&w)) / denominator;
+    let s = (normal_b.dot(&w) - ab * normal_a.dot(&w)) / denominator;
+    let point = ((a + normal_a * t) + (b + normal_b * s)) / 2.0;
+
+    let offset = a - point;
+    let radius = (offset - axis * offset.dot(&axis)).norm();
+    if !radius.is_finite() {
+        return None;
+    }
+
+    Some(Primitive::Cylinder {
+        point: array(&point),
+        axis: array(&axis),
+        radius: radius as f32,
+    })
+}
+
+fn vector(point: &[f32; 3]) -> Vector3<f64> {
+    Vector3::new(point[0] as f64, point[1] as f64, point[2] as f64)
+}
+
+fn array(vector: &Vector3<f64>) -> [f32; 3] {
+    [vector.x as f32, vector.y as f32, vector.z as f32]
+}
+
+// xorshift64, enough to pick samples without pulling in a rand dependency.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn sample<const N: usize>(&mut self, indices: &[usize]) -> [usize; N] {
+        std::array::from_fn(|_| indices[(self.next() % indices.len() as u64) as usize])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{needed_iterations, segment, Primitive, RansacParams};
+    use crate::OctreeError;
+
+    // A tilted plane of 400 points followed by 100 points at least one unit off it.
+    fn plane_and_clutter() -> Vec<[f32; 3]> {
+        let height = |x: f32, z: f32| 0.2 * x + 0.1 * z + 1.0;
+        let mut points = Vec::new();
+        for i in 0..20 {
+            for j in 0..20 {
+                let (x, z) = (i as f32 * 0.5, j as f32 * 0.5);
+                points.push([x, height(x, z), z]);
+            }
+        }
+        for i in 0..100 {
+            let (x, z) = ((i * 37 % 100) as f32 * 0.1, (i * 61 % 100) as f32 * 0.1);
+            let offset = 1.0 + (i % 7) as f32 * 0.3;
+            points.push([x, height(x, z) + if i % 2 == 0 { offset } else { -offset }, z]);
+        }
+        points
+    }
+
+    #[test]
+    fn finds_a_plane_and_its_inliers() {
+        let points = plane_and_clutter();
+        let params = RansacParams { max_primitives: 1, ..RansacParams::default() };
+        let segments = segment(&points, None, &params).unwrap();
+        assert_eq!(segments.len(), 1);
+
+        let mut inliers = segments[0].inliers.clone();
+        inliers.sort_unstable();
+        assert_eq!(inliers, (0..400).collect::<Vec<_>>());
+
+        let Primitive::Plane { normal, .. } = segments[0].primitive else {
+            panic!("{:?}", segments[0].primitive);
+        };
+        // Normal of y = 0.2 x + 0.1 z + 1, up to its sign.
+        let expected = [-0.2, 1.0, -0.1].map(|value: f32| value / 1.05f32.sqrt());
+        let cos: f32 = normal.iter().zip(expected).map(|(a, b)| a * b).sum();
+        assert!(cos.abs() > 0.9999, "{normal:?}");
+        assert!(points[..400].iter().all(|point| segments[0].primitive.distance(point) < 1e-4));
+    }
+
+    #[test]
+    fn stops_below_the_minimum_inlier_count() {
+        let points = plane_and_clutter();
+        let params = RansacParams { min_inliers: 401, ..RansacParams::default() };
+        assert!(segment(&points, None, &params).unwrap().is_empty());
+    }
+
+    // Evenly spread points on a sphere, with their outward normals.
+    fn sphere(middle: [f32; 3], radius: f32, count: usize) -> (Vec<[f32; 3]>, Vec<[f32; 3]>) {
+        let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+        (0..count)
+            .map(|i| {
+                let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+                let ring = (1.0 - y * y).sqrt();
+                let angle = golden_angle * i as f32;
+                let normal = [ring * angle.cos(), y, ring * angle.sin()];
+                (std::array::from_fn(|axis| middle[axis] + radius * normal[axis]), normal)
+            })
+            .unzip()
+    }
+
+    #[test]
+    fn finds_a_sphere_next_to_a_plane() {
+        let mut points = plane_and_clutter();
+        let (sphere_points, _) = sphere([5.0, 10.0, 5.0], 2.0, 600);
+        points.extend(sphere_points);
+
+        let params = RansacParams { spheres: true, max_primitives: 2, ..RansacParams::default() };
+        let segments = segment(&points, None, &params).unwrap();
+        assert_eq!(segments.len(), 2);
+
+        let sphere = segments.iter().find(|segment| matches!(segment.primitive, Primitive::Sphere { .. })).unwrap();
+        let Primitive::Sphere { middle, radius } = sphere.primitive else {
+            unreachable!();
+        };
+        assert!((radius - 2.0).abs() < 1e-3, "{radius}");
+        assert!(middle.iter().zip([5.0, 10.0, 5.0]).all(|(a, b)| (a - b).abs() < 1e-3), "{middle:?}");
+        let mut inliers = sphere.inliers.clone();
+        inliers.sort_unstable();
+        assert_eq!(inliers, (500..1100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn finds_a_cylinder_from_normals() {
+        let mut points = Vec::new();
+        let mut normals = Vec::new();
+        for i in 0..30 {
+            let angle = i as f32 * std::f32::consts::TAU / 30.0;
+            let normal = [angle.cos(), angle.sin(), 0.0];
+            for j in 0..20 {
+                points.push([3.0 + 1.5 * normal[0], 3.0 + 1.5 * normal[1], j as f32 * 0.25]);
+                normals.push(normal);
+            }
+        }
+
+        let params = RansacParams { cylinders: true, max_primitives: 1, ..RansacParams::default() };
+        let segments = segment(&points, Some(&normals), &params).unwrap();
+        assert_eq!(segments.len(), 1);
+        let Primitive::Cylinder { point, axis, radius } = segments[0].primitive else {
+            panic!("{:?}", segments[0].primitive);
+        };
+        assert!((radius - 1.5).abs() < 1e-3, "{radius}");
+        assert!(axis[2].abs() > 0.9999, "{axis:?}");
+        assert!((point[0] - 3.0).abs() < 1e-3 && (point[1] - 3.0).abs() < 1e-3, "{point:?}");
+        assert_eq!(segments[0].inliers.len(), points.len());
+    }
+
+    #[test]
+    fn rejects_normals_of_other_points() {
+        let points = plane_and_clutter();
+        let normals = vec![[0.0, 1.0, 0.0]; points.len() - 1];
+        let result = segment(&points, Some(&normals), &RansacParams::default());
+        assert_eq!(result.err(), Some(OctreeError::NormalCountMismatch { points: 500, normals: 499 }));
+    }
+
+    #[test]
+    fn stops_early_once_a_better_sample_is_unlikely() {
+        assert_eq!(needed_iterations(1.0, 3, 0.99), 1);
+        // 1 - 0.5^3 = 0.875, and 0.875^35 < 0.01 < 0.875^34.
+        assert_eq!(needed_iterations(0.5, 3, 0.99), 35);
+        assert_eq!(needed_iterations(0.0, 3, 0.99), usize::MAX);
+    }
+}
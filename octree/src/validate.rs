@@ -0,0 +1,198 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    cube_middle, gen_cube, octree_divide_into_cube, LooseInsertion, NodeKey, Octree, OctreeNode, SphereMode,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    // `cube_points` are not the corners of an axis aligned cube in `gen_cube` order.
+    MalformedCube { key: NodeKey },
+    ChildDoesNotTile { key: NodeKey, child: usize },
+    PointOutsideNode { key: NodeKey, point: [f32; 3] },
+    MiddleMismatch { key: NodeKey, expected: [f32; 3], found: [f32; 3] },
+    RadiusMismatch { key: NodeKey, expected: f32, found: f32 },
+    DataInInnerNode { key: NodeKey, count: usize },
+    DuplicatePoint { point: [f32; 3], first: NodeKey, second: NodeKey },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::MalformedCube { key } => {
+                write!(f, "{key}: cube points do not form an axis aligned cube")
+            }
+            Violation::ChildDoesNotTile { key, child } => {
+                write!(f, "{key}: child {child} does not match its eighth of the parent cube")
+            }
+            Violation::PointOutsideNode { key, point } => {
+                write!(f, "{key}: point {point:?} lies outside of the node")
+            }
+            Violation::MiddleMismatch { key, expected, found } => {
+                write!(f, "{key}: middle is {found:?}, the cube middle is {expected:?}")
+            }
+            Violation::RadiusMismatch { key, expected, found } => {
+                write!(f, "{key}: radius is {found}, the cube needs {expected}")
+            }
+            Violation::DataInInnerNode { key, count } => {
+                write!(f, "{key}: {count} points stored in a node with children")
+            }
+            Violation::DuplicatePoint { point, first, second } => {
+                write!(f, "point {point:?} is stored in both {first} and {second}")
+            }
+        }
+    }
+}
+
+impl Octree {
+    // Checks the structural invariants of the tree, an empty list means the tree is consistent.
+    pub fn validate(&self) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        // Overlapping loose spheres are expected to share points.
+        let check_duplicates = self.layout.sphere_mode != SphereMode::Loose(LooseInsertion::All);
+        let mut seen: HashMap<[u32; 3], NodeKey> = HashMap::new();
+
+        self.for_each_node(|key, node| {
+            let cube_points = node.cube_points();
+            let [min, max] = node.bounds();
+            let size = max[0] - min[0];
+            let tolerance = size.abs() * 1e-5;
+
+            if !same_points(&cube_points, &gen_cube(min[0], min[1], min[2], size), tolerance) {
+                violations.push(Violation::MalformedCube { key });
+            }
+
+            if let Some(nodes) = node.nodes_ref() {
+                let expected = octree_divide_into_cube(&cube_points);
+                for (child, node) in nodes.iter().enumerate() {
+                    if !same_points(&node.cube_points(), &expected[child], tolerance) {
+                        violations.push(Violation::ChildDoesNotTile { key, child });
+                    }
+                }
+
+                if !node.data_points().is_empty() {
+                    violations.push(Violation::DataInInnerNode { key, count: node.data_points().len() });
+                }
+            }
+
+            if let OctreeNode::Sphere(sphere) = node {
+                let middle = cube_middle(&cube_points);
+                if !same_points(&[sphere.middle], &[middle], tolerance) {
+                    violations.push(Violation::MiddleMismatch { key, expected: middle, found: sphere.middle });
+                }
+
+                let radius = match self.layout.sphere_mode {
+                    SphereMode::Inscribed => size / 2.0,
+                    SphereMode::Loose(_) => size * 3f32.sqrt() / 2.0,
+                };
+                if (sphere.radius - radius).abs() > tolerance {
+                    violations.push(Violation::RadiusMismatch { key, expected: radius, found: sphere.radius });
+                }
+            }
+
+            for data_point in node.data_points() {
+                if !node.holds(data_point, self.layout.sphere_mode) {
+                    violations.push(Violation::PointOutsideNode { key, point: *data_point });
+                }
+
+                if check_duplicates {
+                    if let Some(first) = seen.insert(data_point.map(|value| (value + 0.0).to_bits()), key) {
+                        if first != key {
+                            violations.push(Violation::DuplicatePoint { point: *data_point, first, second: key });
+                        }
+                    }
+                }
+            }
+        });
+
+        violations
+    }
+}
+
+fn same_points(a: &[[f32; 3]], b: &[[f32; 3]], tolerance: f32) -> bool {
+    a.iter()
+        .zip(b.iter())
+        .all(|(a, b)| (0..3).all(|axis| (a[axis] - b[axis]).abs() <= tolerance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Violation;
+    use crate::{LooseInsertion, NodeKey, Octree, OctreeNode, OctreeSphere, SphereMode};
+
+    fn tree() -> Octree {
+        let mut tree = Octree::builder(8.0)
+            .sphere_mode(SphereMode::Loose(LooseInsertion::BestFit))
+            .build()
+            .unwrap();
+        tree.import(&[[0.5, 0.5, 0.5], [7.5, 7.5, 7.5], [3.0, 5.0, 1.0]]);
+        tree
+    }
+
+    fn sphere<'a>(tree: &'a mut Octree, key: &NodeKey) -> &'a mut OctreeSphere {
+        match tree.get_mut(key) {
+            Some(OctreeNode::Sphere(sphere)) => sphere,
+            node => panic!("{key} is not a sphere: {node:?}"),
+        }
+    }
+
+    #[test]
+    fn consistent_tree_has_no_violations() {
+        assert_eq!(tree().validate(), Vec::new());
+    }
+
+    #[test]
+    fn reports_a_wrong_radius() {
+        let mut tree = tree();
+        let key = NodeKey::new(3, 2, 3, 4);
+        let leaf = sphere(&mut tree, &key);
+        let expected = leaf.radius;
+        leaf.radius *= 2.0;
+
+        assert_eq!(tree.validate(), vec![Violation::RadiusMismatch { key, expected, found: expected * 2.0 }]);
+    }
+
+    #[test]
+    fn reports_a_point_outside_of_its_node() {
+        let mut tree = tree();
+        let key = NodeKey::new(3, 0, 0, 0);
+        sphere(&mut tree, &key).data_points.push([4.0, 4.0, 4.0]);
+
+        assert_eq!(tree.validate(), vec![Violation::PointOutsideNode { key, point: [4.0, 4.0, 4.0] }]);
+    }
+
+    #[test]
+    fn reports_misplaced_children_and_points_in_inner_nodes() {
+        let mut tree = tree();
+        let key = NodeKey::new(2, 1, 1, 1);
+        let inner = sphere(&mut tree, &key);
+        inner.data_points.push([2.5, 2.5, 2.5]);
+        inner.nodes.as_mut().unwrap().swap(0, 7);
+
+        let violations = tree.validate();
+        assert!(violations.contains(&Violation::DataInInnerNode { key, count: 1 }), "{violations:?}");
+        assert!(violations.contains(&Violation::ChildDoesNotTile { key, child: 0 }), "{violations:?}");
+        assert!(violations.contains(&Violation::ChildDoesNotTile { key, child: 7 }), "{violations:?}");
+        assert!(!violations.iter().any(|violation| matches!(violation, Violation::ChildDoesNotTile { child: 1..=6, .. })));
+    }
+
+    #[test]
+    fn reports_malformed_cubes_and_duplicates() {
+        let mut tree = tree();
+        let key = NodeKey::new(3, 7, 7, 7);
+        sphere(&mut tree, &key).cube_points[6] = [9.0, 9.0, 9.0];
+        // The point is already stored at (0, 0, 0).
+        let copy = NodeKey::new(3, 0, 0, 1);
+        sphere(&mut tree, &copy).data_points.push([0.5, 0.5, 0.5]);
+
+        let violations = tree.validate();
+        assert!(violations.contains(&Violation::MalformedCube { key }), "{violations:?}");
+        let child = key.child_index().unwrap();
+        assert!(violations.contains(&Violation::ChildDoesNotTile { key: key.parent().unwrap(), child }), "{violations:?}");
+        assert!(violations.contains(&Violation::DuplicatePoint {
+            point: [0.5, 0.5, 0.5],
+            first: NodeKey::new(3, 0, 0, 0),
+            second: copy,
+        }), "{violations:?}");
+    }
+}